@@ -0,0 +1,75 @@
+use std::fmt::{Display, Formatter};
+use crate::tree_enum::TreesEnum;
+use crate::tree_index::TreeIndex;
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum CycleError {
+    /// Cycles go through the center and a node of every branch and can't visit a Steiner point,
+    /// so their length has to be between `shortest` and `longest`
+    InvalidLength {
+        length: usize,
+        shortest: usize,
+        longest: usize,
+    },
+    /// The branch has no nodes, or its corner isn't connected to the center, so there is no start cycle
+    BrokenBranch(TreesEnum),
+    /// The quadrilateral from `center_triangle` crosses itself in every order that was tried,
+    /// so the greedy passes never start
+    SelfCrossingStart {
+        start: Vec<TreeIndex>,
+    },
+    /// The greedy search got to a cycle of `length` nodes, but an edge of the start cycle
+    /// that is still part of it crosses the tree
+    StartCrossesTree {
+        start: Vec<TreeIndex>,
+        length: usize,
+    },
+    /// Both greedy passes got stuck at a cycle of `length` nodes
+    Stalled {
+        length: usize,
+        unreachable: Vec<TreeIndex>,
+    },
+    /// The exhaustive search proved that there is no valid cycle
+    NoCycle,
+    /// The search was stopped from outside before it finished
+    Cancelled,
+}
+
+impl CycleError {
+    /// The length of the longest cycle the search got to, if it got to one at all
+    pub fn reached_length(&self) -> Option<usize> {
+        match self {
+            CycleError::StartCrossesTree { length, .. } => Some(*length),
+            CycleError::Stalled { length, .. } => Some(*length),
+            CycleError::InvalidLength { .. } | CycleError::BrokenBranch(_) | CycleError::SelfCrossingStart { .. } | CycleError::NoCycle | CycleError::Cancelled => None,
+        }
+    }
+}
+
+impl Display for CycleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CycleError::InvalidLength { length, shortest, longest } => {
+                write!(f, "no cycle has length {}, lengths go from {} to {}", length, shortest, longest)
+            }
+            CycleError::BrokenBranch(branch) => {
+                write!(f, "{:?} is empty or its corner isn't connected to the center", branch)
+            }
+            CycleError::SelfCrossingStart { start } => {
+                write!(f, "start cycle {:?} crosses itself", start)
+            }
+            CycleError::StartCrossesTree { start, length } => {
+                write!(f, "start cycle {:?} crosses the tree, so does the cycle of length {}", start, length)
+            }
+            CycleError::Stalled { length, unreachable } => {
+                write!(f, "stalled at length {}, unreachable: {:?}", length, unreachable)
+            }
+            CycleError::NoCycle => {
+                write!(f, "no valid cycle exists")
+            }
+            CycleError::Cancelled => {
+                write!(f, "the search was cancelled")
+            }
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use nannou::color::named::*;
+use nannou::color::Srgb;
+use nannou::geom::Point2;
+use crate::tree::Tree;
+
+/// The colours of the branches in the viewer and every export, by name for svg and TikZ.
+pub const BRANCH_COLORS: [(&str, Srgb<u8>); 6] = [
+    ("blue", BLUE),
+    ("green", GREEN),
+    ("red", RED),
+    ("yellow", YELLOW),
+    ("magenta", MAGENTA),
+    ("cyan", CYAN),
+];
+/// Radius of the nodes in the exports, in tree units for svg and in pixels for png
+pub const NODE_RADIUS: f32 = 5.0;
+
+/// The colour of branch `i`, the colours repeat after the sixth branch.
+pub fn branch_color(i: usize) -> (&'static str, Srgb<u8>) {
+    BRANCH_COLORS[i % BRANCH_COLORS.len()]
+}
+
+impl Tree {
+    /// The lowest and highest coordinates of all nodes.
+    pub fn bounds(&self) -> (Point2, Point2) {
+        let (mut min, mut max) = (Point2::splat(f32::INFINITY), Point2::splat(f32::NEG_INFINITY));
+        for index in self.iter() {
+            min = min.min(self[index].pos);
+            max = max.max(self[index].pos);
+        }
+        (min, max)
+    }
+}
@@ -1,33 +1,227 @@
 use nannou::geom::Point2;
+use crate::predicates::orient2d;
 
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq, Debug)]
 pub struct Edge(pub Point2, pub Point2);
 
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum Intersection {
+    None,
+    /// The interiors of the edges cross in a single point
+    Crossing,
+    /// The edges only meet in an endpoint they share
+    Touching,
+    /// An endpoint of one edge lies on the other edge, but isn't shared
+    EndpointOnEdge,
+    /// The edges are collinear and share more than a single point
+    Overlapping,
+    /// Both edges have the same endpoints, like a tree edge that is reused in a cycle
+    Identical,
+}
+
 impl Edge {
+    /// The shortest distance from `point` to any point of the edge.
+    pub fn distance_to(&self, point: Point2) -> f32 {
+        let d = self.1 - self.0;
+        let length = d.length_squared();
+        if length == 0.0 {
+            return point.distance(self.0);
+        }
+        let t = ((point - self.0).dot(d) / length).clamp(0.0, 1.0);
+        point.distance(self.0 + d * t)
+    }
+
     pub fn intersects(&self, e: Edge) -> bool {
-        let p = self.0;
-        let q = e.0;
-        let r = Point2::new(self.1.x - self.0.x, self.1.y - self.0.y);
-        let s = Point2::new(e.1.x - e.0.x, e.1.y - e.0.y);
-        let r_cross_s = r.x * s.y - r.y * s.x;
-        let q_minus_p = Point2::new(q.x - p.x, q.y - p.y);
-        let q_minus_p_cross_r = q_minus_p.x * r.y - q_minus_p.y * r.x;
-
-        if r_cross_s == 0.0 {
-            // Parallel lines
-            if q_minus_p_cross_r == 0.0 {
-                // Collinear lines
-                let t0 = (q.x - p.x) / r.x;
-                let t1 = (q.y - p.y) / r.y;
-                // Check if the ranges of t overlap
-                return (0.0..=1.0).contains(&t0) || (0.0..=1.0).contains(&t1);
+        self.intersection(e) != Intersection::None
+    }
+
+    /// Checks if two edges cross, edges that only meet in a shared endpoint or are the same edge don't count.
+    pub fn crosses(&self, e: Edge) -> bool {
+        !matches!(self.intersection(e), Intersection::None | Intersection::Touching | Intersection::Identical)
+    }
+
+    pub fn intersection(&self, e: Edge) -> Intersection {
+        let (p0, p1, q0, q1) = (self.0, self.1, e.0, e.1);
+        if (p0 == q0 && p1 == q1) || (p0 == q1 && p1 == q0) {
+            return Intersection::Identical;
+        }
+        let o1 = orient2d(p0, p1, q0);
+        let o2 = orient2d(p0, p1, q1);
+        let o3 = orient2d(q0, q1, p0);
+        let o4 = orient2d(q0, q1, p1);
+
+        if o1 == 0.0 && o2 == 0.0 && o3 == 0.0 && o4 == 0.0 {
+            return self.collinear_intersection(e);
+        }
+        if o1.signum() != o2.signum() && o1 != 0.0 && o2 != 0.0
+            && o3.signum() != o4.signum() && o3 != 0.0 && o4 != 0.0 {
+            return Intersection::Crossing;
+        }
+
+        let mut output = Intersection::None;
+        for (orientation, point, other) in [(o1, q0, *self), (o2, q1, *self), (o3, p0, e), (o4, p1, e)] {
+            if orientation != 0.0 || !other.in_bounds(point) {
+                continue;
             }
-            return false;
+            if point == other.0 || point == other.1 {
+                output = Intersection::Touching;
+            } else {
+                return Intersection::EndpointOnEdge;
+            }
+        }
+        output
+    }
+
+    /// Checks if a point that is known to be on the line through this edge lies on the edge itself.
+    fn in_bounds(&self, point: Point2) -> bool {
+        self.0.x.min(self.1.x) <= point.x && point.x <= self.0.x.max(self.1.x)
+            && self.0.y.min(self.1.y) <= point.y && point.y <= self.0.y.max(self.1.y)
+    }
+
+    fn collinear_intersection(&self, e: Edge) -> Intersection {
+        if self.0 == self.1 && e.0 == e.1 {
+            return if self.0 == e.0 { Intersection::Touching } else { Intersection::None };
+        }
+        // Project on the axis along which the shared line isn't constant
+        let axis = |p: Point2| if self.0.x != self.1.x || e.0.x != e.1.x { p.x } else { p.y };
+        let (p0, p1, q0, q1) = (axis(self.0), axis(self.1), axis(e.0), axis(e.1));
+        let low = p0.min(p1).max(q0.min(q1));
+        let high = p0.max(p1).min(q0.max(q1));
+        if low > high {
+            Intersection::None
+        } else if low < high {
+            Intersection::Overlapping
+        } else if (low == p0 || low == p1) && (low == q0 || low == q1) {
+            Intersection::Touching
+        } else {
+            Intersection::EndpointOnEdge
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn edge(x0: f32, y0: f32, x1: f32, y1: f32) -> Edge {
+        Edge(Point2::new(x0, y0), Point2::new(x1, y1))
+    }
+
+    #[test]
+    fn crossing() {
+        assert_eq!(edge(0.0, 0.0, 2.0, 2.0).intersection(edge(0.0, 2.0, 2.0, 0.0)), Intersection::Crossing);
+    }
+
+    #[test]
+    fn shared_endpoint_touches() {
+        assert_eq!(edge(0.0, 0.0, 2.0, 0.0).intersection(edge(2.0, 0.0, 2.0, 2.0)), Intersection::Touching);
+    }
+
+    #[test]
+    fn endpoint_in_the_middle_of_an_edge() {
+        assert_eq!(edge(0.0, 0.0, 2.0, 0.0).intersection(edge(1.0, 0.0, 1.0, 2.0)), Intersection::EndpointOnEdge);
+    }
+
+    #[test]
+    fn separate_edges() {
+        assert_eq!(edge(0.0, 0.0, 2.0, 0.0).intersection(edge(0.0, 1.0, 2.0, 1.0)), Intersection::None);
+        assert_eq!(edge(0.0, 0.0, 1.0, 1.0).intersection(edge(3.0, 0.0, 2.0, 1.0)), Intersection::None);
+    }
+
+    #[test]
+    fn collinear_edges() {
+        let a = edge(0.0, 0.0, 2.0, 2.0);
+        assert_eq!(a.intersection(edge(1.0, 1.0, 3.0, 3.0)), Intersection::Overlapping);
+        assert_eq!(a.intersection(edge(2.0, 2.0, 3.0, 3.0)), Intersection::Touching);
+        assert_eq!(a.intersection(edge(3.0, 3.0, 4.0, 4.0)), Intersection::None);
+        assert_eq!(edge(0.0, 0.0, 0.0, 2.0).intersection(edge(0.0, 1.0, 0.0, 5.0)), Intersection::Overlapping);
+    }
+
+    #[test]
+    fn identical_edges() {
+        let a = edge(0.0, 0.0, 2.0, 1.0);
+        assert_eq!(a.intersection(a), Intersection::Identical);
+        assert_eq!(a.intersection(Edge(a.1, a.0)), Intersection::Identical);
+    }
+
+    #[test]
+    fn crosses_ignores_shared_endpoints_and_reused_edges() {
+        let a = edge(0.0, 0.0, 2.0, 2.0);
+        assert!(a.crosses(edge(0.0, 2.0, 2.0, 0.0)));
+        assert!(a.crosses(edge(1.0, 1.0, 1.0, 5.0)));
+        assert!(!a.crosses(edge(2.0, 2.0, 2.0, 5.0)));
+        assert!(!a.crosses(Edge(a.1, a.0)));
+    }
+
+    #[test]
+    fn nearly_collinear_edges() {
+        // One ulp off the line through the other edge
+        let a = edge(0.5, 0.5, 12.0, 12.0);
+        let above = Point2::new(24.0, f32::from_bits(24.0f32.to_bits() + 1));
+        assert_eq!(a.intersection(Edge(Point2::new(12.0, 12.0), above)), Intersection::Touching);
+        assert_eq!(a.intersection(edge(6.0, 6.0, 24.0, 24.0)), Intersection::Overlapping);
+        assert_eq!(edge(0.5, 0.5, 24.0, 24.0).intersection(Edge(Point2::new(12.0, 12.0), above)), Intersection::EndpointOnEdge);
+    }
+
+    #[test]
+    fn distance() {
+        let a = edge(0.0, 0.0, 4.0, 0.0);
+        assert_eq!(a.distance_to(Point2::new(2.0, 3.0)), 3.0);
+        assert_eq!(a.distance_to(Point2::new(7.0, 4.0)), 5.0);
+    }
+
+    /// Integer coordinates, so translating and scaling them is exact
+    fn point() -> impl Strategy<Value = Point2> {
+        (-1000i32..1000, -1000i32..1000).prop_map(|(x, y)| Point2::new(x as f32, y as f32))
+    }
+
+    fn any_edge() -> impl Strategy<Value = Edge> {
+        (point(), point()).prop_map(|(a, b)| Edge(a, b))
+    }
+
+    proptest! {
+        #[test]
+        fn intersects_is_symmetric(a in any_edge(), b in any_edge()) {
+            prop_assert_eq!(a.intersects(b), b.intersects(a));
+            prop_assert_eq!(a.intersection(b), b.intersection(a));
+            prop_assert_eq!(a.intersection(b), Edge(a.1, a.0).intersection(b));
+        }
+
+        #[test]
+        fn translation_doesnt_change_intersections(a in any_edge(), b in any_edge(), offset in point()) {
+            let moved = |e: Edge| Edge(e.0 + offset, e.1 + offset);
+            prop_assert_eq!(a.intersection(b), moved(a).intersection(moved(b)));
         }
 
-        let t = (q_minus_p_cross_r) / r_cross_s;
-        let u = (q_minus_p.x * s.y - q_minus_p.y * s.x) / r_cross_s;
+        #[test]
+        fn scaling_doesnt_change_intersections(a in any_edge(), b in any_edge(), factor in prop_oneof![-64i32..-1, 1i32..64]) {
+            let scaled = |e: Edge| Edge(e.0 * factor as f32, e.1 * factor as f32);
+            prop_assert_eq!(a.intersection(b), scaled(a).intersection(scaled(b)));
+        }
 
-        (0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u)
+        #[test]
+        fn collinear_edges_intersect_when_they_overlap(
+            origin in point(),
+            direction in point().prop_filter("direction can't be zero", |d| *d != Point2::ZERO),
+            steps in [-8i32..8, -8i32..8, -8i32..8, -8i32..8],
+        ) {
+            let [s0, s1, t0, t1] = steps;
+            prop_assume!(s0 != s1 && t0 != t1);
+            let at = |step: i32| origin + direction * step as f32;
+            let (a, b) = (Edge(at(s0), at(s1)), Edge(at(t0), at(t1)));
+            let low = s0.min(s1).max(t0.min(t1));
+            let high = s0.max(s1).min(t0.max(t1));
+            let expected = if (s0 == t0 && s1 == t1) || (s0 == t1 && s1 == t0) {
+                Intersection::Identical
+            } else if low < high {
+                Intersection::Overlapping
+            } else if low == high {
+                Intersection::Touching
+            } else {
+                Intersection::None
+            };
+            prop_assert_eq!(a.intersection(b), expected);
+        }
     }
 }
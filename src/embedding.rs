@@ -0,0 +1,333 @@
+use nannou::geom::Point2;
+use crate::edge::{Edge, Intersection};
+use crate::predicates::orient2d;
+use crate::tree::Tree;
+use crate::tree_enum::TreesEnum;
+use crate::tree_index::TreeIndex;
+
+/// Something that makes the tree itself invalid input for the cycle searches.
+#[derive(Clone, PartialEq, Debug)]
+pub enum EmbeddingProblem {
+    /// Two tree edges cross or overlap
+    CrossingEdges(Edge, Edge),
+    /// A node that isn't a corner lies outside the polygon spanned by the corners
+    OutsideBounds(TreeIndex),
+    /// Two nodes are in exactly the same place
+    CoincidentNodes(TreeIndex, TreeIndex),
+}
+
+impl Tree {
+    /// Checks that the tree is drawn without crossings inside its polygon and returns every problem it finds.
+    pub fn check_embedding(&self) -> Vec<EmbeddingProblem> {
+        let mut output = vec![];
+
+        // Edges of length zero come from coincident nodes, which are reported on their own
+        let grid = self.edge_grid();
+        let edges = grid.edges();
+        for (i, a) in edges.iter().enumerate() {
+            if a.0 == a.1 {
+                continue;
+            }
+            let mut crossing = vec![];
+            grid.any_index(*a, |j| {
+                if j > i && edges[j].0 != edges[j].1 && a.crosses(edges[j]) {
+                    crossing.push(j);
+                }
+                false
+            });
+            crossing.sort_unstable();
+            crossing.dedup();
+            output.extend(crossing.into_iter().map(|j| EmbeddingProblem::CrossingEdges(*a, edges[j])));
+        }
+
+        let corners = self.start_cycle();
+        let polygon: Vec<Point2> = corners[1..].iter().map(|index| self[*index].pos).collect();
+        for index in self.iter() {
+            if !corners[1..].contains(&index) && !in_polygon(self[index].pos, &polygon) {
+                output.push(EmbeddingProblem::OutsideBounds(index));
+            }
+        }
+
+        let mut nodes: Vec<TreeIndex> = self.iter().collect();
+        nodes.sort_by(|a, b| {
+            let (a, b) = (self[*a].pos, self[*b].pos);
+            a.x.total_cmp(&b.x).then(a.y.total_cmp(&b.y))
+        });
+        for pair in nodes.windows(2) {
+            if self[pair[0]].pos == self[pair[1]].pos {
+                output.push(EmbeddingProblem::CoincidentNodes(pair[0], pair[1]));
+            }
+        }
+        output
+    }
+
+    /// Brings `problems` from `check_embedding` up to date after the node at `index` moved away from `old_pos`,
+    /// only the edges of the node are checked again. Moving a corner or a node that shares its place with
+    /// another one needs the full check.
+    pub fn update_embedding(&self, problems: &mut Vec<EmbeddingProblem>, index: TreeIndex, old_pos: Point2) {
+        let pos = self[index].pos;
+        let corners = self.start_cycle();
+        let coincident = problems.iter().any(|problem| matches!(problem, EmbeddingProblem::CoincidentNodes(..)))
+            || self.iter().any(|other| other != index && self[other].pos == pos);
+        if corners[1..].contains(&index) || coincident {
+            *problems = self.check_embedding();
+            return;
+        }
+
+        // Without coincident nodes no edge has length zero and every edge is known by its ends
+        let neighbours = self.neighbours(index);
+        let mut own = vec![];
+        let mut old_edges = vec![];
+        for neighbour in neighbours {
+            if neighbour == self.parent_of(index) {
+                own.push(self.edge_index(index));
+                old_edges.push(Edge(old_pos, self[neighbour].pos));
+            } else {
+                own.push(self.edge_index(neighbour));
+                old_edges.push(Edge(self[neighbour].pos, old_pos));
+            }
+        }
+        problems.retain(|problem| match problem {
+            EmbeddingProblem::CrossingEdges(a, b) => !old_edges.contains(a) && !old_edges.contains(b),
+            EmbeddingProblem::OutsideBounds(other) => *other != index,
+            EmbeddingProblem::CoincidentNodes(..) => true,
+        });
+
+        let grid = self.edge_grid();
+        let edges = grid.edges();
+        for i in &own {
+            let mut crossing = vec![];
+            grid.any_index(edges[*i], |j| {
+                // A pair of the node's own edges is found from both sides
+                if j != *i && !(own.contains(&j) && j < *i) && edges[*i].crosses(edges[j]) {
+                    crossing.push(j);
+                }
+                false
+            });
+            crossing.sort_unstable();
+            crossing.dedup();
+            problems.extend(crossing.into_iter().map(|j| EmbeddingProblem::CrossingEdges(edges[j.min(*i)], edges[j.max(*i)])));
+        }
+
+        let polygon: Vec<Point2> = corners[1..].iter().map(|c| self[*c].pos).collect();
+        if !in_polygon(pos, &polygon) {
+            problems.push(EmbeddingProblem::OutsideBounds(index));
+        }
+    }
+
+    /// Moves a node unless that adds embedding problems, returns whether it moved.
+    pub fn try_move_node(&mut self, index: TreeIndex, pos: Point2) -> bool {
+        if self.move_adds_problems(index, pos) {
+            return false;
+        }
+        self.move_node(index, pos);
+        true
+    }
+
+    /// Checks if moving the node at `index` to `pos` would add a problem that involves the node,
+    /// without moving it. Only the edges of the node are checked, against the edge grid.
+    pub fn move_adds_problems(&self, index: TreeIndex, pos: Point2) -> bool {
+        let before = self.local_problems(index, self[index].pos);
+        self.local_problems(index, pos).iter().any(|problem| !before.contains(problem))
+    }
+
+    /// Checks if splitting the edge from `below` to its parent with a node at `pos` would add a problem,
+    /// without inserting the node. Crossings the split edge already had don't count.
+    pub fn insert_adds_problems(&self, below: TreeIndex, pos: Point2) -> bool {
+        let parent = self.parent_of(below);
+        let old = Edge(self[below].pos, self[parent].pos);
+        let new_edges: Vec<Edge> = [below, parent].iter()
+            .map(|end| Edge(pos, self[*end].pos))
+            .filter(|edge| edge.0 != edge.1)
+            .collect();
+        for edge in &new_edges {
+            let crosses = self.edge_grid().any(*edge, |other| {
+                other.0 != other.1 && old.intersection(*other) != Intersection::Identical && edge.crosses(*other) && !old.crosses(*other)
+            });
+            if crosses {
+                return true;
+            }
+        }
+        if new_edges.len() == 2 && new_edges[0].crosses(new_edges[1]) {
+            return true;
+        }
+        let polygon: Vec<Point2> = self.start_cycle()[1..].iter().map(|c| self[*c].pos).collect();
+        !in_polygon(pos, &polygon) || self.iter().any(|other| self[other].pos == pos)
+    }
+
+    /// The problems the node at `index` would have at `pos`.
+    fn local_problems(&self, index: TreeIndex, pos: Point2) -> Vec<LocalProblem> {
+        let mut output = vec![];
+        let neighbours = self.neighbours(index);
+        let old_edges: Vec<Edge> = neighbours.iter().map(|n| Edge(self[index].pos, self[*n].pos)).collect();
+        let new_edges: Vec<(TreeIndex, Edge)> = neighbours.iter()
+            .map(|n| (*n, Edge(pos, self[*n].pos)))
+            .filter(|(_, edge)| edge.0 != edge.1)
+            .collect();
+        for (i, (neighbour, edge)) in new_edges.iter().enumerate() {
+            self.edge_grid().any(*edge, |other| {
+                let own = old_edges.iter().any(|old| old.intersection(*other) == Intersection::Identical);
+                let problem = LocalProblem::Crossing(*neighbour, *other);
+                if !own && other.0 != other.1 && edge.crosses(*other) && !output.contains(&problem) {
+                    output.push(problem);
+                }
+                false
+            });
+            for (other_neighbour, other) in &new_edges[i + 1..] {
+                if edge.crosses(*other) {
+                    output.push(LocalProblem::Crossing(*neighbour, Edge(self[*other_neighbour].pos, pos)));
+                }
+            }
+        }
+
+        // Moving a corner changes the polygon, so every node has to be checked again
+        let corners = self.start_cycle();
+        let polygon: Vec<Point2> = corners[1..].iter().map(|c| if *c == index { pos } else { self[*c].pos }).collect();
+        let outside: Vec<TreeIndex> = if corners[1..].contains(&index) { self.iter().collect() } else { vec![index] };
+        for other in outside {
+            let at = if other == index { pos } else { self[other].pos };
+            if !corners[1..].contains(&other) && !in_polygon(at, &polygon) {
+                output.push(LocalProblem::Outside(other));
+            }
+        }
+
+        for other in self.iter() {
+            if other != index && self[other].pos == pos {
+                output.push(LocalProblem::Coincident(other));
+            }
+        }
+        output
+    }
+
+    /// The nodes the node at `index` shares a tree edge with.
+    fn neighbours(&self, index: TreeIndex) -> Vec<TreeIndex> {
+        match index.0 {
+            TreesEnum::Center => self.iter().filter(|other| other.0 != TreesEnum::Center && self[*other].parent.is_none()).collect(),
+            TreesEnum::Branch(_) => {
+                let mut output = vec![self.parent_of(index)];
+                output.extend(self.children(index).into_iter().map(|child| TreeIndex(index.0, child)));
+                output
+            }
+        }
+    }
+
+    /// The position in `get_all_edges` of the edge from a branch node to its parent.
+    fn edge_index(&self, index: TreeIndex) -> usize {
+        let TreesEnum::Branch(branch) = index.0 else {
+            panic!("the center has no parent");
+        };
+        self.branches()[..branch].iter().map(|nodes| nodes.len()).sum::<usize>() + index.1
+    }
+
+    /// The node at the other end of the edge from a branch node to its parent, which might be the center.
+    fn parent_of(&self, index: TreeIndex) -> TreeIndex {
+        match self[index].parent {
+            Some(parent) => TreeIndex(index.0, parent),
+            None => TreeIndex(TreesEnum::Center, 0),
+        }
+    }
+}
+
+/// A problem of a single node, crossings are known by the other end of the node's edge and the edge it crosses,
+/// so the problems before and after a move can be compared.
+#[derive(PartialEq, Debug)]
+enum LocalProblem {
+    Crossing(TreeIndex, Edge),
+    Outside(TreeIndex),
+    Coincident(TreeIndex),
+}
+
+/// Winding number test, points on the border count as inside.
+pub fn in_polygon(point: Point2, polygon: &[Point2]) -> bool {
+    let mut winding = 0;
+    for (i, a) in polygon.iter().enumerate() {
+        let b = polygon[(i + 1) % polygon.len()];
+        let orientation = orient2d(*a, b, point);
+        if orientation == 0.0 && point.cmpge(a.min(b)).all() && point.cmple(a.max(b)).all() {
+            return true;
+        }
+        if a.y <= point.y {
+            if b.y > point.y && orientation > 0.0 {
+                winding += 1;
+            }
+        } else if b.y <= point.y && orientation < 0.0 {
+            winding -= 1;
+        }
+    }
+    winding != 0
+}
+
+impl EmbeddingProblem {
+    /// The nodes involved in the problem, crossings are only known by their edges.
+    pub fn nodes(&self) -> Vec<TreeIndex> {
+        match self {
+            EmbeddingProblem::CrossingEdges(_, _) => vec![],
+            EmbeddingProblem::OutsideBounds(index) => vec![*index],
+            EmbeddingProblem::CoincidentNodes(a, b) => vec![*a, *b],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nannou::geom::Point2;
+    use proptest::prelude::*;
+    use crate::node::Node;
+    use crate::random::random_trees;
+    use crate::tree_enum::TreesEnum;
+    use crate::tree_index::TreeIndex;
+
+    proptest! {
+        #[test]
+        fn local_check_matches_the_full_check(
+            tree in random_trees(1..5, 3..6),
+            node in any::<prop::sample::Index>(),
+            x in -20..20i32,
+            y in -20..20i32,
+        ) {
+            // Random trees have no problems, so every problem after the move is a new one
+            prop_assert!(tree.check_embedding().is_empty());
+            let nodes: Vec<TreeIndex> = tree.iter().collect();
+            let index = nodes[node.index(nodes.len())];
+            let pos = tree[index].pos + Point2::new(x as f32, y as f32) * 10.0;
+            let mut moved = tree.clone();
+            moved.move_node(index, pos);
+            prop_assert_eq!(tree.move_adds_problems(index, pos), !moved.check_embedding().is_empty());
+        }
+
+        #[test]
+        fn moving_nodes_keeps_the_problems_up_to_date(
+            mut tree in random_trees(1..5, 3..6),
+            moves in prop::collection::vec((any::<prop::sample::Index>(), -20..20i32, -20..20i32), 1..5),
+        ) {
+            tree.embedding_problems();
+            let nodes: Vec<TreeIndex> = tree.iter().collect();
+            for (node, x, y) in moves {
+                let index = nodes[node.index(nodes.len())];
+                let pos = tree[index].pos + Point2::new(x as f32, y as f32) * 10.0;
+                tree.move_node(index, pos);
+                let mut kept = tree.embedding_problems().to_vec();
+                let mut full = tree.check_embedding();
+                prop_assert_eq!(kept.len(), full.len());
+                kept.retain(|problem| !full.contains(problem));
+                full.retain(|problem| !tree.embedding_problems().contains(problem));
+                prop_assert!(kept.is_empty() && full.is_empty(), "only kept: {:?}, only full: {:?}", kept, full);
+            }
+        }
+
+        #[test]
+        fn insert_check_matches_the_full_check(
+            tree in random_trees(1..5, 3..6),
+            node in any::<prop::sample::Index>(),
+            x in -20..20i32,
+            y in -20..20i32,
+        ) {
+            let nodes: Vec<TreeIndex> = tree.iter().filter(|index| index.0 != TreesEnum::Center).collect();
+            let below = nodes[node.index(nodes.len())];
+            let pos = tree[below].pos + Point2::new(x as f32, y as f32) * 10.0;
+            let mut inserted = tree.clone();
+            inserted.insert_node(below.1, below.0, Node::from_pos(pos.x, pos.y));
+            prop_assert_eq!(tree.insert_adds_problems(below, pos), !inserted.check_embedding().is_empty());
+        }
+    }
+}
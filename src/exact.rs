@@ -0,0 +1,161 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use crate::cycle_error::CycleError;
+use crate::edge::Edge;
+use crate::tree::Tree;
+use crate::tree_index::TreeIndex;
+
+struct Search<'a> {
+    tree: &'a Tree,
+    nodes: Vec<TreeIndex>,
+    visible: Vec<Vec<bool>>,
+    visited: Vec<bool>,
+    path: Vec<usize>,
+    cancel: &'a AtomicBool,
+    progress: &'a mut dyn FnMut(usize),
+    longest: usize,
+}
+
+impl Tree {
+    /// Backtracking search for a cycle through every node except the Steiner points that only uses edges which don't cross the tree.
+    /// Unlike `find_cycle` this never gives up early, an `Err` means no such cycle exists.
+    pub fn find_cycle_exact(&self) -> Result<Vec<TreeIndex>, CycleError> {
+        self.find_cycle_exact_cancellable(&AtomicBool::new(false), &mut |_| {})
+    }
+
+    /// `find_cycle_exact` that stops with `CycleError::Cancelled` once `cancel` is set
+    /// and calls `progress` whenever the search finds a longer path than before.
+    pub fn find_cycle_exact_cancellable(&self, cancel: &AtomicBool, progress: &mut dyn FnMut(usize)) -> Result<Vec<TreeIndex>, CycleError> {
+        let nodes: Vec<TreeIndex> = self.cycle_nodes().collect();
+        if nodes.len() < 3 {
+            return Err(CycleError::NoCycle);
+        }
+        let graph = self.visibility();
+        let visible = nodes.iter()
+            .map(|a| nodes.iter().map(|b| graph.is_visible(*a, *b)).collect())
+            .collect();
+        let mut search = Search {
+            tree: self,
+            visited: vec![false; nodes.len()],
+            path: vec![0],
+            nodes,
+            visible,
+            cancel,
+            progress,
+            longest: 0,
+        };
+        search.visited[0] = true;
+        if search.extend() {
+            Ok(search.path.iter().map(|i| search.nodes[*i]).collect())
+        } else if cancel.load(Ordering::Relaxed) {
+            Err(CycleError::Cancelled)
+        } else {
+            Err(CycleError::NoCycle)
+        }
+    }
+}
+
+impl Search<'_> {
+    fn edge(&self, a: usize, b: usize) -> Edge {
+        Edge(self.tree[self.nodes[a]].pos, self.tree[self.nodes[b]].pos)
+    }
+
+    /// Checks the new edge against all edges already in the path,
+    /// `skip_first` is used for the closing edge which touches the first edge of the path.
+    fn crosses_path(&self, a: usize, b: usize, skip_first: bool) -> bool {
+        let new_edge = self.edge(a, b);
+        let start = if skip_first { 1 } else { 0 };
+        for i in start..self.path.len().saturating_sub(2) {
+            if new_edge.crosses(self.edge(self.path[i], self.path[i + 1])) {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Every node that is still unvisited needs two free neighbours to ever be part of the cycle.
+    fn is_dead_end(&self) -> bool {
+        let first = self.path[0];
+        let last = *self.path.last().unwrap();
+        for node in 0..self.nodes.len() {
+            if self.visited[node] {
+                continue;
+            }
+            let free = (0..self.nodes.len())
+                .filter(|other| self.visible[node][*other])
+                .filter(|other| !self.visited[*other] || *other == first || *other == last)
+                .count();
+            if free < 2 {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn extend(&mut self) -> bool {
+        if self.cancel.load(Ordering::Relaxed) {
+            return false;
+        }
+        if self.path.len() > self.longest {
+            self.longest = self.path.len();
+            (self.progress)(self.longest);
+        }
+        let last = *self.path.last().unwrap();
+        if self.path.len() == self.nodes.len() {
+            let first = self.path[0];
+            return self.visible[last][first] && !self.crosses_path(last, first, true);
+        }
+        if self.is_dead_end() {
+            return false;
+        }
+        for next in 0..self.nodes.len() {
+            if self.visited[next] || !self.visible[last][next] {
+                continue;
+            }
+            // A cycle can be walked both ways, only search one of the directions
+            if self.path.len() == self.nodes.len() - 1 && next < self.path[1] {
+                continue;
+            }
+            if self.crosses_path(last, next, false) {
+                continue;
+            }
+            self.visited[next] = true;
+            self.path.push(next);
+            if self.extend() {
+                return true;
+            }
+            self.path.pop();
+            self.visited[next] = false;
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use proptest::prelude::*;
+    use crate::random::random_trees;
+    use crate::tree::Tree;
+    use crate::validate::Violation;
+
+    #[test]
+    fn exact_cycles_can_visit_a_branch_twice() {
+        let tree = Tree::random(&[3, 3, 3], 2);
+        let cycle = tree.find_cycle_exact().unwrap();
+        assert_eq!(tree.validate_hamiltonian_cycle(&cycle), vec![]);
+        assert!(tree.validate_greedy_cycle(&cycle).iter().any(|violation| matches!(violation, Violation::ColourJump(..))));
+    }
+
+    proptest! {
+        #[test]
+        fn exact_cycles_are_valid_hamiltonian_cycles(tree in random_trees(1..4, 3..5)) {
+            let exact = tree.find_cycle_exact();
+            if let Ok(cycle) = &exact {
+                prop_assert_eq!(tree.validate_hamiltonian_cycle(cycle), vec![]);
+            }
+            // A full greedy cycle is a valid solution as well, so the exact search can't prove there is none
+            if let Ok(cycle) = tree.find_cycle(tree.full_cycle_len()) {
+                prop_assert!(exact.is_ok(), "greedy found {:?}, exact returned {:?}", cycle, exact);
+            }
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use std::ops::Range;
+use std::time::{Duration, Instant};
+use crate::tree::Tree;
+
+/// The result of running the greedy `find_cycle` on a single random tree.
+#[derive(Clone, Debug)]
+pub struct Outcome {
+    pub seed: u64,
+    pub nodes: usize,
+    pub found: bool,
+    pub max_length: usize,
+    pub runtime: Duration,
+}
+
+impl Outcome {
+    pub fn csv_header() -> &'static str {
+        "seed,nodes,found,max_length,micros"
+    }
+
+    pub fn to_csv(&self) -> String {
+        format!("{},{},{},{},{}", self.seed, self.nodes, self.found, self.max_length, self.runtime.as_micros())
+    }
+}
+
+/// Tries to find a hamiltonian cycle in the random tree generated from `counts` and `seed`.
+pub fn run_instance(counts: &[usize], seed: u64) -> Outcome {
+    let tree = Tree::random(counts, seed);
+    let nodes = tree.full_cycle_len();
+    let start = Instant::now();
+    let result = tree.find_cycle(nodes);
+    let runtime = start.elapsed();
+    Outcome {
+        seed,
+        nodes,
+        found: result.is_ok(),
+        max_length: match result {
+            Ok(cycle) => cycle.len(),
+            Err(err) => err.reached_length().unwrap_or(0),
+        },
+        runtime,
+    }
+}
+
+pub fn run_batch(counts: &[usize], seeds: Range<u64>) -> Vec<Outcome> {
+    seeds.map(|seed| run_instance(counts, seed)).collect()
+}
@@ -0,0 +1,152 @@
+use crate::cycle_error::CycleError;
+use crate::tree::{Tree, Triangle};
+use crate::tree_index::TreeIndex;
+
+/// One step of the greedy cycle construction in `find_cycle`.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Step {
+    /// The cycle the search starts from
+    Start(Vec<TreeIndex>),
+    /// `node` can be seen from `edge.0` but not from `edge.1`, so it is skipped
+    Candidate {
+        edge: (TreeIndex, TreeIndex),
+        node: TreeIndex,
+    },
+    /// `node` was inserted into the cycle at `position`, between the nodes of `edge`
+    Insert {
+        edge: (TreeIndex, TreeIndex),
+        node: TreeIndex,
+        position: usize,
+    },
+    /// The strict pass got stuck, from now on triangles with one odd node are allowed as well
+    Relaxed,
+    Finished(Result<Vec<TreeIndex>, CycleError>),
+}
+
+/// The greedy search as an iterator over its steps, ends after `Step::Finished`.
+pub struct GreedySteps<'a> {
+    tree: &'a Tree,
+    length: usize,
+    start: Vec<TreeIndex>,
+    /// Why the search can't start, growing a crossing start can't give a valid cycle
+    error: Option<CycleError>,
+    cycle: Vec<TreeIndex>,
+    strict: bool,
+    /// The cycle edge currently being extended, starting at `cycle[prev]`
+    prev: usize,
+    candidates: Option<std::vec::IntoIter<TreeIndex>>,
+    other_vis: Option<Vec<TreeIndex>>,
+    started: bool,
+    finished: bool,
+}
+
+impl Tree {
+    pub fn greedy_steps(&self, length: usize) -> GreedySteps<'_> {
+        let (shortest, longest) = self.cycle_lengths();
+        let (start, error) = match self.center_triangle() {
+            Err(err) => (vec![], Some(err)),
+            Ok(_) if length < shortest || length > longest => (vec![], Some(CycleError::InvalidLength { length, shortest, longest })),
+            Ok(start) if !self.is_simple_cycle(&start) => (start.clone(), Some(CycleError::SelfCrossingStart { start })),
+            Ok(start) => (start, None),
+        };
+        GreedySteps {
+            tree: self,
+            length,
+            error,
+            cycle: start.clone(),
+            start,
+            strict: true,
+            prev: 0,
+            candidates: None,
+            other_vis: None,
+            started: false,
+            finished: false,
+        }
+    }
+}
+
+impl GreedySteps<'_> {
+    /// The cycle as far as it is built.
+    pub fn current_cycle(&self) -> &[TreeIndex] {
+        &self.cycle
+    }
+
+    fn finish(&mut self, result: Result<Vec<TreeIndex>, CycleError>) -> Option<Step> {
+        self.finished = true;
+        Some(Step::Finished(result))
+    }
+}
+
+impl Iterator for GreedySteps<'_> {
+    type Item = Step;
+
+    fn next(&mut self) -> Option<Step> {
+        if self.finished {
+            return None;
+        }
+        if !self.started {
+            self.started = true;
+            return Some(Step::Start(self.cycle.clone()));
+        }
+        if let Some(err) = self.error.take() {
+            return self.finish(Err(err));
+        }
+        let tree = self.tree;
+        loop {
+            if self.cycle.len() == self.length {
+                // Inserted edges never cross the tree, but edges of the start cycle that are still there can
+                if tree.cycle_crosses_tree(&self.cycle) {
+                    let start = self.start.clone();
+                    return self.finish(Err(CycleError::StartCrossesTree { start, length: self.cycle.len() }));
+                }
+                return self.finish(Ok(self.cycle.clone()));
+            }
+            let next = (self.prev + 1) % self.cycle.len();
+            if self.candidates.is_none() {
+                if self.prev == self.cycle.len() {
+                    // A whole pass without inserting anything
+                    if self.strict {
+                        self.strict = false;
+                        self.prev = 0;
+                        return Some(Step::Relaxed);
+                    } else {
+                        let unreachable = tree.cycle_nodes().filter(|index| !self.cycle.contains(index)).collect();
+                        return self.finish(Err(CycleError::Stalled { length: self.cycle.len(), unreachable }));
+                    }
+                }
+                // if this is the strict pass the edges must be of the same edge
+                if !self.cycle[self.prev].same_color(self.cycle[next]) && self.strict {
+                    self.prev += 1;
+                    continue;
+                }
+                // Get a list of all visible nodes from the first node
+                self.candidates = Some(tree.check_node_vis_cycle_org(self.cycle[self.prev], &self.cycle).into_iter());
+                self.other_vis = None;
+            }
+
+            let edge = (self.cycle[self.prev], self.cycle[next]);
+            let Some(visible_node) = self.candidates.as_mut().and_then(|candidates| candidates.next()) else {
+                self.candidates = None;
+                self.prev += 1;
+                continue;
+            };
+            match visible_node.makes_triangle((edge.1, edge.0)) {
+                Triangle::AllSame => {}
+                Triangle::OneOdd if !self.strict => {}
+                _ => continue,
+            }
+            if self.cycle.contains(&visible_node) {
+                continue;
+            }
+            let cycle = &self.cycle;
+            let other_vis = self.other_vis.get_or_insert_with(|| tree.check_node_vis_cycle_org(edge.1, cycle));
+            if !other_vis.contains(&visible_node) {
+                return Some(Step::Candidate { edge, node: visible_node });
+            }
+            self.cycle.insert(next, visible_node);
+            self.candidates = None;
+            self.prev = 0;
+            return Some(Step::Insert { edge, node: visible_node, position: next });
+        }
+    }
+}
@@ -0,0 +1,250 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::Path;
+use triangles::cycle_error::CycleError;
+use triangles::experiment::{Outcome, run_batch};
+use triangles::shrink::{greedy_error, greedy_fails_like, shrink};
+use triangles::{raster, tikz};
+use triangles::tree::Tree;
+use triangles::tree_index::TreeIndex;
+
+const USAGE: &str = "usage: triangles solve <file> [cycle length]";
+const SHRINK_USAGE: &str = "usage: triangles shrink <output file> (<file> | <seed> <nodes per branch>...)";
+const SVG_USAGE: &str = "usage: triangles svg <file> <output file> [cycle length]";
+const TIKZ_USAGE: &str = "usage: triangles tikz <file> <output file> [size in cm] [cycle length]";
+const PNG_USAGE: &str = "usage: triangles png <file> <output file> [size in pixels] [cycle length]";
+const BATCH_USAGE: &str = "usage: triangles batch <instances> <nodes per branch>... <csv file> [first seed]";
+
+/// Runs `find_cycle` on a saved tree without opening a window and returns the exit code.
+/// Without a cycle length every length from the amount of branches plus one up to the amount of nodes
+/// that aren't Steiner points is tried.
+pub fn solve(args: &[String]) -> i32 {
+    let Some(file) = args.first() else {
+        eprintln!("{}", USAGE);
+        return 1;
+    };
+    let tree = match Tree::load(Path::new(file)) {
+        Ok(tree) => tree,
+        Err(err) => {
+            eprintln!("Could not load {}: {}", file, err);
+            return 1;
+        }
+    };
+    let (shortest, longest) = tree.cycle_lengths();
+    let lengths = match args.get(1).map(|arg| arg.parse::<usize>()) {
+        Some(Ok(length)) if length < shortest || length > longest => {
+            eprintln!("{}", CycleError::InvalidLength { length, shortest, longest });
+            return 1;
+        }
+        Some(Ok(length)) => vec![length],
+        Some(Err(_)) => {
+            eprintln!("{}", USAGE);
+            return 1;
+        }
+        None => (shortest..=longest).collect(),
+    };
+
+    for problem in tree.check_embedding() {
+        eprintln!("warning: {:?}", problem);
+    }
+    println!("special nodes: {:?}", tree.find_special_nodes());
+    let mut code = 0;
+    for length in lengths {
+        match tree.find_cycle(length) {
+            Ok(cycle) => println!("{}: {:?}", length, cycle),
+            Err(err) => {
+                println!("{}: {}", length, err);
+                code = 2;
+            }
+        }
+    }
+    code
+}
+
+/// Draws a saved tree with the cycle of the given length, or of all nodes, to an svg file.
+/// When there is no such cycle the tree is drawn without one.
+pub fn export_svg(args: &[String]) -> i32 {
+    let (Some(file), Some(output)) = (args.first(), args.get(1)) else {
+        eprintln!("{}", SVG_USAGE);
+        return 1;
+    };
+    let Some((tree, cycle)) = load_with_cycle(file, args.get(2), SVG_USAGE) else {
+        return 1;
+    };
+    if let Err(err) = tree.save_svg(Path::new(output), cycle.as_deref()) {
+        eprintln!("Could not write {}: {}", output, err);
+        return 1;
+    }
+    0
+}
+
+/// Writes a saved tree and its cycle as a TikZ picture, like `export_svg`.
+pub fn export_tikz(args: &[String]) -> i32 {
+    let size = match args.get(2).map(|arg| arg.parse::<f32>()) {
+        Some(Ok(size)) if size > 0.0 => size,
+        None => tikz::DEFAULT_SIZE,
+        _ => {
+            eprintln!("{}", TIKZ_USAGE);
+            return 1;
+        }
+    };
+    let (Some(file), Some(output)) = (args.first(), args.get(1)) else {
+        eprintln!("{}", TIKZ_USAGE);
+        return 1;
+    };
+    let Some((tree, cycle)) = load_with_cycle(file, args.get(3), TIKZ_USAGE) else {
+        return 1;
+    };
+    if let Err(err) = tree.save_tikz(Path::new(output), cycle.as_deref(), size) {
+        eprintln!("Could not write {}: {}", output, err);
+        return 1;
+    }
+    0
+}
+
+/// Renders a saved tree and its cycle to a png file without a window, like `export_svg`.
+pub fn export_png(args: &[String]) -> i32 {
+    let size = match args.get(2).map(|arg| arg.parse::<u32>()) {
+        Some(Ok(size)) if size > 0 => size,
+        None => raster::DEFAULT_SIZE,
+        _ => {
+            eprintln!("{}", PNG_USAGE);
+            return 1;
+        }
+    };
+    let (Some(file), Some(output)) = (args.first(), args.get(1)) else {
+        eprintln!("{}", PNG_USAGE);
+        return 1;
+    };
+    let Some((tree, cycle)) = load_with_cycle(file, args.get(3), PNG_USAGE) else {
+        return 1;
+    };
+    if let Err(err) = tree.save_png(Path::new(output), cycle.as_deref(), size) {
+        eprintln!("Could not write {}: {}", output, err);
+        return 1;
+    }
+    0
+}
+
+/// Loads a tree for the exporters and looks for a cycle of `length`, or of all nodes.
+fn load_with_cycle(file: &str, length: Option<&String>, usage: &str) -> Option<(Tree, Option<Vec<TreeIndex>>)> {
+    let tree = match Tree::load(Path::new(file)) {
+        Ok(tree) => tree,
+        Err(err) => {
+            eprintln!("Could not load {}: {}", file, err);
+            return None;
+        }
+    };
+    let (shortest, longest) = tree.cycle_lengths();
+    let length = match length.map(|arg| arg.parse::<usize>()) {
+        Some(Ok(length)) if length < shortest || length > longest => {
+            eprintln!("{}", CycleError::InvalidLength { length, shortest, longest });
+            return None;
+        }
+        Some(Ok(length)) => length,
+        Some(Err(_)) => {
+            eprintln!("{}", usage);
+            return None;
+        }
+        None => longest,
+    };
+    let cycle = match tree.find_cycle(length) {
+        Ok(cycle) => Some(cycle),
+        Err(err) => {
+            eprintln!("No cycle of length {}: {}", length, err);
+            None
+        }
+    };
+    Some((tree, cycle))
+}
+
+/// Runs the greedy search on a range of seeded random trees and writes one csv row per tree.
+pub fn batch(args: &[String]) -> i32 {
+    // The csv file is the first argument after the instances that isn't a number, the node counts come before it
+    let Some(file_at) = args.iter().skip(1).position(|arg| arg.parse::<usize>().is_err()).map(|i| i + 1) else {
+        eprintln!("{}", BATCH_USAGE);
+        return 1;
+    };
+    let file = &args[file_at];
+    let start_seed = match args.get(file_at + 1).map(|arg| arg.parse::<u64>()) {
+        Some(Ok(seed)) if args.len() == file_at + 2 => seed,
+        None => 0,
+        _ => {
+            eprintln!("{}", BATCH_USAGE);
+            return 1;
+        }
+    };
+    let (Some(instances), Some(counts)) = (args[0].parse::<u64>().ok(), parse_counts(args.get(1..file_at))) else {
+        eprintln!("{}", BATCH_USAGE);
+        return 1;
+    };
+    let Some(end_seed) = start_seed.checked_add(instances) else {
+        eprintln!("{} instances starting at seed {} go past the largest seed {}", instances, start_seed, u64::MAX);
+        return 1;
+    };
+
+    let outcomes = run_batch(&counts, start_seed..end_seed);
+    if let Err(err) = write_csv(Path::new(file), &outcomes) {
+        eprintln!("Could not write {}: {}", file, err);
+        return 1;
+    }
+
+    let failed: Vec<u64> = outcomes.iter().filter(|outcome| !outcome.found).map(|outcome| outcome.seed).collect();
+    println!("found a cycle in {} of {} instances", outcomes.len() - failed.len(), outcomes.len());
+    if !failed.is_empty() {
+        println!("failing seeds: {:?}", failed);
+    }
+    0
+}
+
+fn write_csv(path: &Path, outcomes: &[Outcome]) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    writeln!(writer, "{}", Outcome::csv_header())?;
+    for outcome in outcomes {
+        writeln!(writer, "{}", outcome.to_csv())?;
+    }
+    writer.flush()
+}
+
+/// Shrinks a tree on which the greedy search fails to a smaller tree where it still fails with the same kind of error.
+pub fn shrink_failure(args: &[String]) -> i32 {
+    let seed = args.get(1).and_then(|arg| arg.parse::<u64>().ok());
+    let tree = match (args.len(), seed, parse_counts(args.get(2..))) {
+        (2, None, _) => match Tree::load(Path::new(&args[1])) {
+            Ok(tree) => tree,
+            Err(err) => {
+                eprintln!("Could not load {}: {}", args[1], err);
+                return 1;
+            }
+        },
+        (_, Some(seed), Some(counts)) => Tree::random(&counts, seed),
+        _ => {
+            eprintln!("{}", SHRINK_USAGE);
+            return 1;
+        }
+    };
+    let Some(error) = greedy_error(&tree) else {
+        eprintln!("The greedy search finds a cycle, there is nothing to shrink");
+        return 1;
+    };
+
+    let smallest = shrink(&tree, greedy_fails_like(&error));
+    println!("shrunk from {} to {} nodes", tree.iter().len(), smallest.iter().len());
+    if let Some(error) = greedy_error(&smallest) {
+        println!("{}", error);
+    }
+    if let Err(err) = smallest.save(Path::new(&args[0])) {
+        eprintln!("Could not save {}: {}", args[0], err);
+        return 1;
+    }
+    0
+}
+
+/// Parses the amount of nodes in every branch, there have to be at least 3 branches with a node each.
+fn parse_counts(args: Option<&[String]>) -> Option<Vec<usize>> {
+    let counts: Vec<usize> = args?.iter().map(|arg| arg.parse().ok()).collect::<Option<_>>()?;
+    if counts.len() < 3 || counts.contains(&0) {
+        return None;
+    }
+    Some(counts)
+}
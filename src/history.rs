@@ -0,0 +1,116 @@
+use nannou::geom::Point2;
+use crate::node::Node;
+use crate::tree::Tree;
+use crate::tree_enum::TreesEnum;
+use crate::tree_index::TreeIndex;
+
+/// A single edit of a tree that can be applied and reverted.
+#[derive(Clone, Debug)]
+pub enum Edit {
+    Move {
+        index: TreeIndex,
+        from: Point2,
+        to: Point2,
+    },
+    /// `node` ends up at `index` and becomes the parent of `children`, see `Tree::restore_node`
+    Insert {
+        index: TreeIndex,
+        node: Node,
+        children: Vec<usize>,
+    },
+    Remove {
+        index: TreeIndex,
+        node: Node,
+        children: Vec<usize>,
+    },
+    /// Moves the node at position `from` in the branch along the tree to position `to`
+    Reorder {
+        branch: TreesEnum,
+        from: usize,
+        to: usize,
+    },
+    /// Turns the node into a Steiner point or back, reverting does the same
+    Steiner {
+        index: TreeIndex,
+    },
+}
+
+impl Edit {
+    pub fn apply(&self, tree: &mut Tree) {
+        match self {
+            Edit::Move { index, to, .. } => tree.move_node(*index, *to),
+            Edit::Insert { index, node, children } => tree.restore_node(*index, *node, children),
+            Edit::Remove { index, .. } => {
+                tree.take_node(*index);
+            }
+            Edit::Reorder { branch, from, to } => {
+                tree.reorder_node(TreeIndex(*branch, *from), *to);
+            }
+            Edit::Steiner { index } => {
+                tree.set_steiner(*index, !tree[*index].steiner);
+            }
+        }
+    }
+
+    pub fn revert(&self, tree: &mut Tree) {
+        match self {
+            Edit::Move { index, from, .. } => tree.move_node(*index, *from),
+            Edit::Insert { index, .. } => {
+                tree.take_node(*index);
+            }
+            Edit::Remove { index, node, children } => tree.restore_node(*index, *node, children),
+            Edit::Reorder { branch, from, to } => Edit::Reorder { branch: *branch, from: *to, to: *from }.apply(tree),
+            Edit::Steiner { .. } => self.apply(tree),
+        }
+    }
+}
+
+/// Undo and redo stacks of the edits that were done on a tree.
+pub struct History {
+    undo: Vec<Edit>,
+    redo: Vec<Edit>,
+}
+
+impl History {
+    pub fn new() -> Self {
+        Self {
+            undo: vec![],
+            redo: vec![],
+        }
+    }
+
+    /// Records an edit that has already been applied to the tree.
+    pub fn push(&mut self, edit: Edit) {
+        self.undo.push(edit);
+        self.redo.clear();
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    pub fn undo(&mut self, tree: &mut Tree) -> bool {
+        let Some(edit) = self.undo.pop() else {
+            return false;
+        };
+        edit.revert(tree);
+        self.redo.push(edit);
+        true
+    }
+
+    pub fn redo(&mut self, tree: &mut Tree) -> bool {
+        let Some(edit) = self.redo.pop() else {
+            return false;
+        };
+        edit.apply(tree);
+        self.undo.push(edit);
+        true
+    }
+}
+
+impl Default for History {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,23 @@
+pub mod tree;
+pub mod node;
+pub mod edge;
+pub mod tree_index;
+pub mod tree_enum;
+pub mod exact;
+pub mod cycle_error;
+pub mod predicates;
+pub mod save;
+pub mod validate;
+pub mod random;
+pub mod experiment;
+pub mod shrink;
+pub mod history;
+pub mod spatial;
+pub mod visibility;
+pub mod solver;
+pub mod greedy;
+pub mod svg;
+pub mod tikz;
+pub mod raster;
+pub mod drawing;
+pub mod embedding;
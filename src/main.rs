@@ -1,41 +1,81 @@
-mod tree;
-mod node;
 mod renderer;
-mod edge;
-mod tree_index;
-mod tree_enum;
+mod headless;
 
 use std::f32::consts::SQRT_2;
+use std::path::PathBuf;
+use std::process::exit;
 use nannou::App;
 use nannou::geom::Point2;
-use tree_enum::TreesEnum;
-use crate::node::Node;
-use crate::renderer::{event, Model, RenderOptions, view};
-use crate::tree::Tree;
+use triangles::history::History;
+use triangles::node::Node;
+use triangles::tree::Tree;
+use triangles::tree_enum::TreesEnum;
+use crate::renderer::{Cache, event, Model, RenderOptions, view};
 
 
-pub fn model(_app: &App) -> Model {
-    let mut t = Tree::empty();
+pub fn default_tree() -> Tree {
+    let mut t = Tree::empty(3);
     let total = 1;
     for i in 0..total {
         let dist = 200.0 / total as f32;
-        t.add_node(TreesEnum::First, Node::from_pos(0.0, -dist - (i as f32 * dist)));
-        t.add_node(TreesEnum::Second, Node::from_pos(-dist - (i as f32 * dist) / SQRT_2, dist + (i as f32 * dist) / SQRT_2));
-        t.add_node(TreesEnum::Third, Node::from_pos(dist + (i as f32 * dist) / SQRT_2, dist + (i as f32 * dist) / SQRT_2));
+        t.add_node(TreesEnum::Branch(0), Node::from_pos(0.0, -dist - (i as f32 * dist)));
+        t.add_node(TreesEnum::Branch(1), Node::from_pos(-dist - (i as f32 * dist) / SQRT_2, dist + (i as f32 * dist) / SQRT_2));
+        t.add_node(TreesEnum::Branch(2), Node::from_pos(dist + (i as f32 * dist) / SQRT_2, dist + (i as f32 * dist) / SQRT_2));
     }
-    let cycle_len = t.iter().len();
+    t
+}
+
+pub fn model(app: &App) -> Model {
+    // Escape cancels the coordinate entry, the event handler quits when there is none
+    app.set_exit_on_escape(false);
+    // s saves to and l loads from the file on the command line, which doesn't have to exist yet
+    let arg = std::env::args().nth(1).map(PathBuf::from);
+    let (t, status) = match &arg {
+        Some(path) if path.exists() => {
+            let t = Tree::load(path).unwrap_or_else(|err| {
+                eprintln!("Could not load {}: {}", path.display(), err);
+                exit(1);
+            });
+            (t, Some(format!("Loaded {}", path.display())))
+        }
+        Some(path) => (default_tree(), Some(format!("{} doesn't exist yet, s saves there", path.display()))),
+        None => (default_tree(), None),
+    };
+    let cycle_len = t.full_cycle_len();
     Model {
         tree: t,
         camera: Point2::new(0.0, 200.0),
         scale: 1.0,
         selected: None,
+        drag_start: None,
+        drag_offset: Point2::ZERO,
         click: (0.0, 0.0).into(),
         cycle_len,
+        file: arg.unwrap_or_else(|| PathBuf::from("triangle.json")),
+        seed: 0,
+        corners: 3,
+        history: History::new(),
         render_options: RenderOptions::default(),
+        cache: Cache::new(),
+        animation: None,
+        coordinate_entry: None,
+        status: status.map(|text| (text, 0.0)),
+        pending_export: None,
+        pending_capture: None,
     }
 }
 
 fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("solve") => exit(headless::solve(&args[1..])),
+        Some("batch") => exit(headless::batch(&args[1..])),
+        Some("shrink") => exit(headless::shrink_failure(&args[1..])),
+        Some("svg") => exit(headless::export_svg(&args[1..])),
+        Some("tikz") => exit(headless::export_tikz(&args[1..])),
+        Some("png") => exit(headless::export_png(&args[1..])),
+        _ => {}
+    }
     nannou::app(model)
         .event(event)
         .simple_window(view)
@@ -4,16 +4,22 @@ use nannou::geom::Point2;
 #[derive(Debug, Copy, Clone)]
 pub struct Node {
     pub pos: Point2,
+    /// Index of the parent in the same branch, `None` if the node is connected to the center
+    pub parent: Option<usize>,
+    /// A Steiner point only shapes the tree, cycles don't visit it
+    pub steiner: bool,
 }
 
 
-impl Node {
-    pub fn default() -> Self {
-        Node {pos: (0.0, 0.0).into()}
+impl Default for Node {
+    fn default() -> Self {
+        Node {pos: (0.0, 0.0).into(), parent: None, steiner: false}
     }
+}
 
+impl Node {
     pub fn from_pos(x: f32, y: f32) -> Self {
-        Node {pos: Point2::new(x, y)}
+        Node {pos: Point2::new(x, y), parent: None, steiner: false}
     }
     
     pub fn dist(&self, other: &Self) -> f32 {
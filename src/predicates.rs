@@ -0,0 +1,115 @@
+use nannou::geom::Point2;
+
+// Adaptive precision orientation test in the style of Shewchuk's "Robust Predicates".
+// The determinant is first computed in plain f64 and only recomputed exactly
+// with floating point expansions when the result is too close to zero to trust.
+
+const EPSILON: f64 = f64::EPSILON / 2.0;
+const CCW_ERR_BOUND: f64 = (3.0 + 16.0 * EPSILON) * EPSILON;
+
+/// Returns a positive value if `a`, `b` and `c` are in counterclockwise order,
+/// a negative value if they are clockwise and exactly zero if they are collinear.
+pub fn orient2d(a: Point2, b: Point2, c: Point2) -> f64 {
+    let (ax, ay) = (a.x as f64, a.y as f64);
+    let (bx, by) = (b.x as f64, b.y as f64);
+    let (cx, cy) = (c.x as f64, c.y as f64);
+
+    let left = (ax - cx) * (by - cy);
+    let right = (ay - cy) * (bx - cx);
+    let det = left - right;
+    let err_bound = CCW_ERR_BOUND * (left.abs() + right.abs());
+    if det.abs() > err_bound || (det == 0.0 && err_bound == 0.0) {
+        return det;
+    }
+    orient2d_exact(ax, ay, bx, by, cx, cy)
+}
+
+fn orient2d_exact(ax: f64, ay: f64, bx: f64, by: f64, cx: f64, cy: f64) -> f64 {
+    let acx = two_diff(ax, cx);
+    let bcy = two_diff(by, cy);
+    let acy = two_diff(ay, cy);
+    let bcx = two_diff(bx, cx);
+    let left = expansion_product(&acx, &bcy);
+    let right = expansion_product(&acy, &bcx);
+    let det = right.iter().fold(left, |sum, r| grow_expansion(&sum, -r));
+    det.last().copied().unwrap_or(0.0)
+}
+
+fn two_sum(a: f64, b: f64) -> (f64, f64) {
+    let x = a + b;
+    let b_virtual = x - a;
+    let a_virtual = x - b_virtual;
+    (x, (a - a_virtual) + (b - b_virtual))
+}
+
+fn two_diff(a: f64, b: f64) -> [f64; 2] {
+    let (x, y) = two_sum(a, -b);
+    [y, x]
+}
+
+fn two_product(a: f64, b: f64) -> (f64, f64) {
+    let x = a * b;
+    (x, a.mul_add(b, -x))
+}
+
+/// Adds a single value to a nonoverlapping expansion, dropping zero components.
+fn grow_expansion(e: &[f64], b: f64) -> Vec<f64> {
+    let mut output = Vec::with_capacity(e.len() + 1);
+    let mut q = b;
+    for component in e {
+        let (sum, err) = two_sum(q, *component);
+        q = sum;
+        if err != 0.0 {
+            output.push(err);
+        }
+    }
+    if q != 0.0 || output.is_empty() {
+        output.push(q);
+    }
+    output
+}
+
+fn expansion_product(e: &[f64], f: &[f64]) -> Vec<f64> {
+    let mut output = vec![];
+    for a in e {
+        for b in f {
+            let (x, y) = two_product(*a, *b);
+            output = grow_expansion(&output, y);
+            output = grow_expansion(&output, x);
+        }
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        /// Coordinates are integers divided by a power of two, so the exact sign can be computed with integers
+        #[test]
+        fn orient2d_has_the_exact_sign(
+            coordinates in [-(1i64 << 24)..(1i64 << 24), -(1i64 << 24)..(1i64 << 24), -(1i64 << 24)..(1i64 << 24),
+                -(1i64 << 24)..(1i64 << 24), -(1i64 << 24)..(1i64 << 24), -(1i64 << 24)..(1i64 << 24)],
+            shift in 0i32..40,
+        ) {
+            let [ax, ay, bx, by, cx, cy] = coordinates;
+            let scale = 2f32.powi(-shift);
+            let point = |x: i64, y: i64| Point2::new(x as f32 * scale, y as f32 * scale);
+            let det = (ax - cx) as i128 * (by - cy) as i128 - (ay - cy) as i128 * (bx - cx) as i128;
+            let orientation = orient2d(point(ax, ay), point(bx, by), point(cx, cy));
+            prop_assert_eq!(orientation.partial_cmp(&0.0), Some(det.cmp(&0)));
+        }
+
+        #[test]
+        fn orient2d_finds_points_just_off_a_line(x in 1i64..(1 << 16), y in 1i64..(1 << 16), steps in 2i64..64, offset in -2i64..=2) {
+            // c is exactly on the line through a and b when offset is zero, else barely next to it
+            let a = Point2::new(0.5, 0.5);
+            let b = Point2::new(0.5 + x as f32, 0.5 + y as f32);
+            let c = Point2::new(0.5 + (x * steps) as f32, 0.5 + (y * steps + offset) as f32);
+            let orientation = orient2d(a, b, c);
+            prop_assert_eq!(orientation.partial_cmp(&0.0), Some(offset.cmp(&0)));
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use std::f32::consts::PI;
+use nannou::geom::Point2;
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use crate::node::Node;
+use crate::tree::Tree;
+use crate::tree_enum::TreesEnum;
+use crate::tree_index::TreeIndex;
+
+/// Distance from the origin to the corners of a generated triangle.
+pub const RADIUS: f32 = 200.0;
+
+impl Tree {
+    /// Generates a random tree in a regular polygon with `counts[i]` nodes in branch `i`, including the corner.
+    /// The same seed and counts always give the same tree, and the tree edges never cross.
+    pub fn random(counts: &[usize], seed: u64) -> Tree {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        loop {
+            let tree = Self::random_attempt(counts, &mut rng);
+            if !tree.tree_edges_cross() {
+                return tree;
+            }
+        }
+    }
+
+    fn random_attempt(counts: &[usize], rng: &mut ChaCha8Rng) -> Tree {
+        let k = counts.len();
+        let corners: Vec<Point2> = (0..k)
+            .map(|i| {
+                let angle = -PI / 2.0 - i as f32 * 2.0 * PI / k as f32;
+                Point2::new(angle.cos(), angle.sin()) * RADIUS
+            })
+            .collect();
+        // Keeping the center close to the centroid keeps every branch region convex
+        let center = Point2::new(rng.gen_range(-0.2..0.2), rng.gen_range(-0.2..0.2)) * RADIUS;
+
+        let mut tree = Tree::empty(k);
+        tree[TreeIndex(TreesEnum::Center, 0)] = Node::from_pos(center.x, center.y);
+        for i in 0..k {
+            let corner = corners[i];
+            let prev_mid = (corner + corners[(i + k - 1) % k]) / 2.0;
+            let next_mid = (corner + corners[(i + 1) % k]) / 2.0;
+            let mut points: Vec<Point2> = (1..counts[i].max(1))
+                .map(|_| {
+                    let mid = if rng.gen_bool(0.5) { prev_mid } else { next_mid };
+                    random_in_triangle(rng, center, corner, mid)
+                })
+                .collect();
+            // A path that is monotone along a direction can't cross itself
+            let direction = corner - center;
+            points.sort_by(|a, b| (*a - center).dot(direction).partial_cmp(&(*b - center).dot(direction)).unwrap());
+            points.push(corner);
+            for point in points {
+                tree.add_node(TreesEnum::Branch(i), Node::from_pos(point.x, point.y));
+            }
+        }
+        tree
+    }
+}
+
+/// Random trees for property tests, with a number of nodes from `nodes` in each of `branches` branches.
+#[cfg(test)]
+pub fn random_trees(nodes: std::ops::Range<usize>, branches: std::ops::Range<usize>) -> impl proptest::strategy::Strategy<Value = Tree> {
+    use proptest::prelude::*;
+    (prop::collection::vec(nodes, branches), any::<u64>()).prop_map(|(counts, seed)| Tree::random(&counts, seed))
+}
+
+fn random_in_triangle(rng: &mut ChaCha8Rng, a: Point2, b: Point2, c: Point2) -> Point2 {
+    let (mut u, mut v): (f32, f32) = (rng.gen(), rng.gen());
+    if u + v > 1.0 {
+        u = 1.0 - u;
+        v = 1.0 - v;
+    }
+    a + (b - a) * u + (c - a) * v
+}
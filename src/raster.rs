@@ -0,0 +1,168 @@
+use std::io::Error;
+use std::path::Path;
+use nannou::color::named::*;
+use nannou::color::Srgb;
+use nannou::geom::Point2;
+use nannou::image::RgbImage;
+use crate::drawing::{branch_color, NODE_RADIUS};
+use crate::edge::Edge;
+use crate::tree::Tree;
+use crate::tree_enum::TreesEnum;
+use crate::tree_index::TreeIndex;
+
+/// Width or height of the image in pixels, whichever is larger
+pub const DEFAULT_SIZE: u32 = 800;
+const MARGIN: f32 = 20.0;
+
+/// 3 by 5 pixel digits for the cycle labels, one row per entry with the leftmost pixel in the highest bit.
+const DIGITS: [[u8; 5]; 10] = [
+    [0b111, 0b101, 0b101, 0b101, 0b111],
+    [0b010, 0b110, 0b010, 0b010, 0b111],
+    [0b111, 0b001, 0b111, 0b100, 0b111],
+    [0b111, 0b001, 0b111, 0b001, 0b111],
+    [0b101, 0b101, 0b111, 0b001, 0b001],
+    [0b111, 0b100, 0b111, 0b001, 0b111],
+    [0b111, 0b100, 0b111, 0b101, 0b111],
+    [0b111, 0b001, 0b010, 0b010, 0b010],
+    [0b111, 0b101, 0b111, 0b101, 0b111],
+    [0b111, 0b101, 0b111, 0b001, 0b111],
+];
+
+/// A software rasterizer for drawing trees without a window or a GPU.
+pub struct Canvas {
+    width: u32,
+    height: u32,
+    pixels: Vec<[f32; 3]>,
+}
+
+impl Canvas {
+    pub fn new(width: u32, height: u32, background: Srgb<u8>) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![to_floats(background); (width * height) as usize],
+        }
+    }
+
+    /// Mixes `color` into the pixel, `coverage` is how much of the pixel the shape covers.
+    fn blend(&mut self, x: i64, y: i64, color: Srgb<u8>, coverage: f32) {
+        if x < 0 || y < 0 || x >= self.width as i64 || y >= self.height as i64 || coverage <= 0.0 {
+            return;
+        }
+        let coverage = coverage.min(1.0);
+        let pixel = &mut self.pixels[(y * self.width as i64 + x) as usize];
+        for (channel, value) in pixel.iter_mut().zip(to_floats(color)) {
+            *channel += (value - *channel) * coverage;
+        }
+    }
+
+    /// Calls `f` with every pixel centre in the box around `a` and `b` grown by `margin`.
+    fn for_box(&mut self, a: Point2, b: Point2, margin: f32, mut f: impl FnMut(&mut Self, i64, i64, Point2)) {
+        let (low, high) = (a.min(b) - Point2::splat(margin), a.max(b) + Point2::splat(margin));
+        for y in low.y.floor() as i64..=high.y.ceil() as i64 {
+            for x in low.x.floor() as i64..=high.x.ceil() as i64 {
+                f(self, x, y, Point2::new(x as f32 + 0.5, y as f32 + 0.5));
+            }
+        }
+    }
+
+    /// An anti-aliased line, positions are in pixels with y pointing down.
+    pub fn line(&mut self, a: Point2, b: Point2, width: f32, color: Srgb<u8>) {
+        self.for_box(a, b, width + 1.0, |canvas, x, y, point| {
+            let distance = Edge(a, b).distance_to(point);
+            canvas.blend(x, y, color, width / 2.0 + 0.5 - distance);
+        });
+    }
+
+    pub fn circle(&mut self, center: Point2, radius: f32, color: Srgb<u8>) {
+        self.for_box(center, center, radius + 1.0, |canvas, x, y, point| {
+            canvas.blend(x, y, color, radius + 0.5 - point.distance(center));
+        });
+    }
+
+    /// Writes a number centred on `center`, every font pixel is `scale` pixels wide.
+    pub fn number(&mut self, center: Point2, number: usize, scale: i64, color: Srgb<u8>) {
+        let text: Vec<usize> = number.to_string().bytes().map(|digit| (digit - b'0') as usize).collect();
+        let width = (text.len() as i64 * 4 - 1) * scale;
+        let left = center.x as i64 - width / 2;
+        let top = center.y as i64 - 5 * scale / 2;
+        for (i, digit) in text.iter().enumerate() {
+            for (row, bits) in DIGITS[*digit].iter().enumerate() {
+                for column in 0..3 {
+                    if bits & (0b100 >> column) == 0 {
+                        continue;
+                    }
+                    for dy in 0..scale {
+                        for dx in 0..scale {
+                            let x = left + (i as i64 * 4 + column) * scale + dx;
+                            let y = top + row as i64 * scale + dy;
+                            self.blend(x, y, color, 1.0);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn to_image(&self) -> RgbImage {
+        let bytes = self.pixels.iter()
+            .flat_map(|pixel| pixel.map(|channel| (channel * 255.0).round().clamp(0.0, 255.0) as u8))
+            .collect();
+        RgbImage::from_raw(self.width, self.height, bytes).unwrap()
+    }
+
+    pub fn save_png(&self, path: &Path) -> Result<(), Error> {
+        self.to_image().save(path).map_err(Error::other)
+    }
+}
+
+fn to_floats(color: Srgb<u8>) -> [f32; 3] {
+    [color.red as f32 / 255.0, color.green as f32 / 255.0, color.blue as f32 / 255.0]
+}
+
+impl Tree {
+    /// Draws the tree like the viewer does, with the largest side of the image `size` pixels long.
+    pub fn to_canvas(&self, cycle: Option<&[TreeIndex]>, size: u32) -> Canvas {
+        let (min, max) = self.bounds();
+        let extent = (max - min).max_element().max(f32::EPSILON);
+        let scale = (size as f32 - 2.0 * MARGIN).max(1.0) / extent;
+        let width = ((max.x - min.x) * scale + 2.0 * MARGIN).ceil() as u32;
+        let height = ((max.y - min.y) * scale + 2.0 * MARGIN).ceil() as u32;
+        // Images have y pointing down
+        let at = |pos: Point2| Point2::new((pos.x - min.x) * scale + MARGIN, (max.y - pos.y) * scale + MARGIN);
+
+        let mut canvas = Canvas::new(width, height, BLACK);
+        let corners = self.start_cycle();
+        for (prev, next) in (1..corners.len()).zip((1..corners.len()).cycle().skip(1)) {
+            canvas.line(at(self[corners[prev]].pos), at(self[corners[next]].pos), 1.0, GRAY);
+        }
+        for edge in self.get_all_edges() {
+            canvas.line(at(edge.0), at(edge.1), 1.0, GRAY);
+        }
+        for index in self.iter() {
+            let color = match index.0 {
+                _ if self[index].steiner => GRAY,
+                TreesEnum::Branch(i) => branch_color(i).1,
+                TreesEnum::Center => GRAY,
+            };
+            let radius = if self[index].steiner { NODE_RADIUS / 2.0 } else { NODE_RADIUS };
+            canvas.circle(at(self[index].pos), radius, color);
+        }
+        if let Some(cycle) = cycle {
+            for (i, index) in cycle.iter().enumerate() {
+                canvas.line(at(self[*index].pos), at(self[cycle[(i + 1) % cycle.len()]].pos), 1.0, PINK);
+            }
+            for (i, index) in cycle.iter().enumerate() {
+                canvas.number(at(self[*index].pos), i, 2, WHITE);
+            }
+        }
+        for index in self.find_special_nodes() {
+            canvas.circle(at(self[index].pos), NODE_RADIUS, DARKCYAN);
+        }
+        canvas
+    }
+
+    pub fn save_png(&self, path: &Path, cycle: Option<&[TreeIndex]>, size: u32) -> Result<(), Error> {
+        self.to_canvas(cycle, size).save_png(path)
+    }
+}
@@ -1,33 +1,199 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, TryRecvError};
+use std::thread;
+use std::time::{SystemTime, UNIX_EPOCH};
 use nannou::prelude::*;
 use nannou::winit::dpi::PhysicalPosition;
 use nannou::winit::event::DeviceEvent;
-use crate::tree::Tree;
-use crate::tree_enum::TreesEnum;
-use crate::tree_index::TreeIndex;
+use triangles::cycle_error::CycleError;
+use triangles::drawing::branch_color;
+use triangles::edge::Edge;
+use triangles::embedding::EmbeddingProblem;
+use triangles::greedy::Step;
+use triangles::history::{Edit, History};
+use triangles::solver::{Solver, Update};
+use triangles::node::Node;
+use triangles::tikz::DEFAULT_SIZE;
+use triangles::tree::Tree;
+use triangles::tree_enum::TreesEnum;
+use triangles::tree_index::TreeIndex;
+use triangles::validate::Violation;
 
 pub struct Model {
     pub tree: Tree,
     pub camera: Point2,
     pub scale: f64,
     pub selected: Option<TreeIndex>,
+    pub drag_start: Option<Point2>,
+    /// How far the mouse moved since the drag started, in tree coordinates
+    pub drag_offset: Point2,
     pub click: Point2,
     pub cycle_len: usize,
+    pub file: PathBuf,
+    pub seed: u64,
+    pub corners: usize,
+    pub history: History,
     pub render_options: RenderOptions,
+    pub cache: Cache,
+    pub animation: Option<Animation>,
+    /// The node whose coordinates are being typed in, with the text typed so far
+    pub coordinate_entry: Option<(TreeIndex, String)>,
+    /// What the last key did, with how many seconds it has been shown
+    pub status: Option<(String, f32)>,
+    /// 'v' or 't' while an export waits for the greedy search to finish
+    pub pending_export: Option<char>,
+    /// A screenshot that is saved from the next frame, with the amount of frames drawn before it.
+    /// Status messages are hidden until it is taken, so they don't end up in the picture
+    pub pending_capture: Option<(PathBuf, u64)>,
+}
+
+impl Model {
+    /// Shows `text` at the top of the window for `STATUS_TIME` seconds.
+    pub fn show_status(&mut self, text: String) {
+        self.status = Some((text, 0.0));
+    }
 }
 
 pub struct RenderOptions {
     show_path: bool,
+    show_exact: bool,
+    /// Dragging a node or typing its coordinates doesn't move it when that would add problems to the embedding of the tree
+    reject_bad_drags: bool,
+    /// Draws the grid and snaps dragged nodes to it
+    snap_to_grid: bool,
+    /// Clicking empty canvas splits the nearest tree edge with a new node
+    place_nodes: bool,
 }
 
 impl RenderOptions {
     pub fn default() -> Self {
         Self {
-            show_path: false
+            show_path: false,
+            show_exact: false,
+            reject_bad_drags: false,
+            snap_to_grid: false,
+            place_nodes: false,
         }
     }
 }
 
+/// Results from the tree that are too slow to compute every frame, refreshed in `Event::Update`.
+/// The cycles are searched on worker threads and stay `None` until the search is done.
+pub struct Cache {
+    /// The tree generation, cycle length, options and selection the results belong to
+    key: Option<(u64, usize, bool, bool, Option<TreeIndex>)>,
+    greedy: Solver,
+    greedy_progress: Option<usize>,
+    cycle: Option<Result<Vec<TreeIndex>, CycleError>>,
+    cycle_violations: Vec<Violation>,
+    exact_solver: Solver,
+    exact_progress: Option<usize>,
+    exact: Option<Result<Vec<TreeIndex>, CycleError>>,
+    exact_violations: Vec<Violation>,
+    special: Vec<TreeIndex>,
+    embedding: Vec<EmbeddingProblem>,
+    selected_vis: Vec<TreeIndex>,
+}
+
+impl Cache {
+    pub fn new() -> Self {
+        Self {
+            key: None,
+            greedy: Solver::greedy(),
+            greedy_progress: None,
+            cycle: None,
+            cycle_violations: vec![],
+            exact_solver: Solver::exact(),
+            exact_progress: None,
+            exact: None,
+            exact_violations: vec![],
+            special: vec![],
+            embedding: vec![],
+            selected_vis: vec![],
+        }
+    }
+}
+
+/// A recorded run of the greedy search that is played back one step at a time.
+/// The steps are recorded on a worker thread and arrive while the animation is already shown.
+pub struct Animation {
+    /// The tree generation the steps were recorded on, the animation stops when the tree changes
+    generation: u64,
+    steps: Vec<Step>,
+    /// `None` once every step has arrived
+    recording: Option<Receiver<Step>>,
+    cancel: Arc<AtomicBool>,
+    /// How many steps are shown
+    position: usize,
+    playing: bool,
+    elapsed: f32,
+}
+
+impl Animation {
+    pub fn record(tree: &Tree, length: usize) -> Self {
+        let (sender, receiver) = channel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let generation = tree.generation();
+        let tree = tree.clone();
+        let stop = cancel.clone();
+        thread::spawn(move || {
+            for step in tree.greedy_steps(length) {
+                if stop.load(Ordering::Relaxed) || sender.send(step).is_err() {
+                    return;
+                }
+            }
+        });
+        Self {
+            generation,
+            steps: vec![],
+            recording: Some(receiver),
+            cancel,
+            position: 1,
+            playing: false,
+            elapsed: 0.0,
+        }
+    }
+
+    /// Takes the steps that were recorded since the last call, without blocking.
+    fn receive(&mut self) {
+        let Some(receiver) = &self.recording else {
+            return;
+        };
+        loop {
+            match receiver.try_recv() {
+                Ok(step) => self.steps.push(step),
+                Err(TryRecvError::Empty) => return,
+                Err(TryRecvError::Disconnected) => {
+                    self.recording = None;
+                    return;
+                }
+            }
+        }
+    }
+
+    fn step(&mut self, forward: bool) {
+        self.position = if forward { (self.position + 1).min(self.steps.len()).max(1) } else { (self.position - 1).max(1) };
+    }
+}
+
+impl Drop for Animation {
+    fn drop(&mut self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
 pub const SIZE: f32 = 5.0;
+pub const RANDOM_NODES: usize = 4;
+/// Distance between the grid lines in tree coordinates
+pub const GRID_SIZE: f32 = 10.0;
+/// How far from a tree edge a click can place a node, in pixels
+pub const PLACE_DISTANCE: f32 = 20.0;
+/// Seconds between two steps of a playing animation
+pub const STEP_TIME: f32 = 0.4;
+/// Seconds a status message stays on screen
+pub const STATUS_TIME: f32 = 5.0;
 
 
 pub fn event(app: &App, model: &mut Model, event: Event) {
@@ -38,40 +204,213 @@ pub fn event(app: &App, model: &mut Model, event: Event) {
             }
             match simple.unwrap() {
                 MousePressed(MouseButton::Left) => {
-                    let data = Point2::new(
-                        app.mouse.position().x / model.scale as f32,
-                        app.mouse.position().y / model.scale as f32)
-                        - model.camera;
+                    let data = cursor_pos(app, model);
                     model.click = data;
                     model.selected = model.tree.find_node_at_pos(data, SIZE / model.scale as f32);
+                    if model.selected.is_none() && model.render_options.place_nodes {
+                        model.selected = place_node(model, data);
+                    }
+                    model.drag_start = model.selected.map(|index| model.tree[index].pos);
+                    model.drag_offset = Point2::ZERO;
                 },
-                MousePressed(MouseButton::Right) => {
-                    let data = Point2::new(
-                        app.mouse.position().x / model.scale as f32,
-                        app.mouse.position().y / model.scale as f32)
-                        - model.camera;
+                MousePressed(MouseButton::Right) if model.selected.is_none() => {
+                    let data = cursor_pos(app, model);
                     model.click = data;
-                    if let Some(node)= model.tree.find_node_at_pos(data, SIZE / model.scale as f32) {
+                    let found = model.tree.find_node_at_pos(data, SIZE / model.scale as f32);
+                    if let Some(node) = found.filter(|node| node.0 != TreesEnum::Center) {
                         model.tree.insert_node(node.1, node.0, model.tree[node]);
+                        model.history.push(Edit::Insert { index: node, node: model.tree[node], children: model.tree.children(node) });
                         model.selected = Some(node);
+                        model.drag_start = Some(model.tree[node].pos);
+                        model.drag_offset = Point2::ZERO;
                     }
                 }
                 MouseReleased(_) => {
+                    if let (Some(index), Some(from)) = (model.selected, model.drag_start) {
+                        let to = model.tree[index].pos;
+                        if to != from {
+                            model.history.push(Edit::Move { index, from, to });
+                        }
+                    }
                     model.selected = None;
+                    model.drag_start = None;
+                }
+                KeyPressed(Key::Back) if model.coordinate_entry.is_some() => {
+                    if let Some((_, text)) = &mut model.coordinate_entry {
+                        text.pop();
+                    }
+                }
+                KeyPressed(Key::Return) => {
+                    if let Some((index, text)) = model.coordinate_entry.take() {
+                        match parse_coordinates(&text) {
+                            Some(to) if index.0 == TreesEnum::Center || index.1 < model.tree[index.0].len() => {
+                                if model.render_options.reject_bad_drags && model.tree.move_adds_problems(index, to) {
+                                    model.show_status(format!("Moving the node to {}, {} would break the embedding", to.x, to.y));
+                                } else {
+                                    let from = model.tree[index].pos;
+                                    model.tree.move_node(index, to);
+                                    model.history.push(Edit::Move { index, from, to });
+                                }
+                            }
+                            _ => model.show_status(format!("Could not read coordinates from \"{}\", expected \"x, y\"", text)),
+                        }
+                    }
+                }
+                ReceivedCharacter(c) if model.coordinate_entry.is_some() => {
+                    if let Some((_, text)) = &mut model.coordinate_entry {
+                        if c.is_ascii_digit() || matches!(c, '-' | '.' | ',' | ' ') {
+                            text.push(c);
+                        }
+                    }
+                }
+                KeyPressed(Key::Escape) => {
+                    if model.coordinate_entry.is_some() {
+                        model.coordinate_entry = None;
+                    } else {
+                        app.quit();
+                    }
+                }
+                // The other keys would edit the tree while its coordinates are being typed
+                KeyPressed(_) if model.coordinate_entry.is_some() => {}
+                // Removing, adding, reordering or undoing would shift the indices of the node being dragged,
+                // right clicking during a drag is ignored for the same reason
+                KeyPressed(Key::Delete | Key::Back | Key::LBracket | Key::RBracket | Key::A | Key::Z) if model.selected.is_some() => {}
+                KeyPressed(Key::Delete) | KeyPressed(Key::Back) => {
+                    if let Some(index) = model.tree.find_node_at_pos(cursor_pos(app, model), SIZE / model.scale as f32) {
+                        if let Some((node, children)) = model.tree.remove_node(index) {
+                            model.history.push(Edit::Remove { index, node, children });
+                            model.cycle_len = model.cycle_len.min(model.tree.full_cycle_len());
+                        }
+                    }
+                }
+                KeyPressed(key @ (Key::LBracket | Key::RBracket)) => {
+                    if let Some(index) = model.tree.find_node_at_pos(cursor_pos(app, model), SIZE / model.scale as f32) {
+                        // [ moves the node towards the center, ] towards the corner
+                        let to = if key == Key::LBracket { model.tree[index].parent } else { model.tree.next_towards_corner(index) };
+                        if let Some(to) = to {
+                            if model.tree.reorder_node(index, to) {
+                                model.history.push(Edit::Reorder { branch: index.0, from: index.1, to });
+                            }
+                        }
+                    }
+                }
+                KeyPressed(Key::A) => {
+                    if let Some(parent) = model.tree.find_node_at_pos(cursor_pos(app, model), SIZE / model.scale as f32) {
+                        let mut node = model.tree[parent];
+                        node.pos.x += 3.0 * SIZE / model.scale as f32;
+                        if let Some(index) = model.tree.add_child(parent, node) {
+                            model.history.push(Edit::Insert { index, node: model.tree[index], children: vec![] });
+                        }
+                    }
+                }
+                KeyPressed(Key::Z) if app.keys.mods.ctrl() => {
+                    if app.keys.mods.shift() {
+                        model.history.redo(&mut model.tree);
+                    } else {
+                        model.history.undo(&mut model.tree);
+                    }
                 }
                 ReceivedCharacter(c) => {
                     match c {
                         'c' => {
                             model.render_options.show_path = !model.render_options.show_path;
                         }
-                        '+' => {
-                            if model.cycle_len != model.tree.iter().len() {
-                                model.cycle_len += 1
+                        'e' => {
+                            model.render_options.show_exact = !model.render_options.show_exact;
+                        }
+                        'i' => {
+                            model.render_options.place_nodes = !model.render_options.place_nodes;
+                            model.show_status(format!("Clicking empty space places nodes: {}", model.render_options.place_nodes));
+                        }
+                        '#' => {
+                            model.render_options.snap_to_grid = !model.render_options.snap_to_grid;
+                        }
+                        '=' => {
+                            if let Some(index) = model.tree.find_node_at_pos(cursor_pos(app, model), SIZE / model.scale as f32) {
+                                let pos = model.tree[index].pos;
+                                model.coordinate_entry = Some((index, format!("{}, {}", pos.x, pos.y)));
+                            }
+                        }
+                        'x' => {
+                            if let Some(index) = model.tree.find_node_at_pos(cursor_pos(app, model), SIZE / model.scale as f32) {
+                                if model.tree.set_steiner(index, !model.tree[index].steiner) {
+                                    model.history.push(Edit::Steiner { index });
+                                    model.cycle_len = model.cycle_len.min(model.tree.full_cycle_len());
+                                } else {
+                                    model.show_status("The center and the corners can't be Steiner points".to_string());
+                                }
+                            }
+                        }
+                        'k' => {
+                            model.render_options.reject_bad_drags = !model.render_options.reject_bad_drags;
+                            model.show_status(format!("Rejecting drags that break the embedding: {}", model.render_options.reject_bad_drags));
+                        }
+                        's' => {
+                            match model.tree.save(&model.file) {
+                                Ok(()) => model.show_status(format!("Saved to {}", model.file.display())),
+                                Err(err) => model.show_status(format!("Could not save {}: {}", model.file.display(), err)),
+                            }
+                        }
+                        'l' => {
+                            match Tree::load(&model.file) {
+                                Ok(tree) => {
+                                    model.tree = tree;
+                                    model.history.clear();
+                                    model.selected = None;
+                                    model.cycle_len = model.tree.full_cycle_len();
+                                    model.show_status(format!("Loaded {}", model.file.display()));
+                                }
+                                Err(err) => model.show_status(format!("Could not load {}: {}", model.file.display(), err)),
+                            }
+                        }
+                        'r' => {
+                            model.seed += 1;
+                            model.show_status(format!("Random tree with seed {}", model.seed));
+                            model.tree = Tree::random(&vec![RANDOM_NODES; model.corners], model.seed);
+                            model.history.clear();
+                            model.selected = None;
+                            model.cycle_len = model.tree.full_cycle_len();
+                        }
+                        '+' if model.cycle_len < model.tree.full_cycle_len() => {
+                            model.cycle_len += 1
+                        }
+                        '-' if model.cycle_len > model.tree.cycle_lengths().0 => {
+                            model.cycle_len -= 1
+                        }
+                        '>' => {
+                            model.corners += 1;
+                            model.show_status(format!("Random trees get {} corners", model.corners));
+                        }
+                        '<' if model.corners > 3 => {
+                            model.corners -= 1;
+                            model.show_status(format!("Random trees get {} corners", model.corners));
+                        }
+                        'v' | 't' => {
+                            // The export happens in `Event::Update` once the greedy search has a result for this tree
+                            model.pending_export = Some(c);
+                            model.show_status("Exporting once the greedy search is done".to_string());
+                        }
+                        'p' => {
+                            let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+                            let path = PathBuf::from(format!("screenshot-{}.png", time.as_millis()));
+                            app.main_window().capture_frame(&path);
+                            model.pending_capture = Some((path, app.elapsed_frames()));
+                        }
+                        'g' => {
+                            model.animation = match model.animation {
+                                Some(_) => None,
+                                None => Some(Animation::record(&model.tree, model.cycle_len)),
+                            };
+                        }
+                        ' ' => {
+                            if let Some(animation) = &mut model.animation {
+                                animation.playing = !animation.playing;
                             }
                         }
-                        '-' => {
-                            if model.cycle_len != 4 {
-                                model.cycle_len -= 1
+                        '.' | ',' => {
+                            if let Some(animation) = &mut model.animation {
+                                animation.playing = false;
+                                animation.step(c == '.');
                             }
                         }
                         _ => {}
@@ -86,46 +425,250 @@ pub fn event(app: &App, model: &mut Model, event: Event) {
                     model.camera.x += (delta.0 / model.scale) as f32;
                     model.camera.y -= (delta.1 / model.scale) as f32;
                 }
-                if app.mouse.buttons.left().is_down() {
-                    if let Some(index) = &model.selected {
-                        model.tree[*index].pos += vec2((delta.0 / model.scale) as f32, -(delta.1 / model.scale) as f32)
-                    }
-                }
-                if app.mouse.buttons.right().is_down() {
-                    if let Some(index) = &model.selected {
-                        model.tree[*index].pos += vec2((delta.0 / model.scale) as f32, -(delta.1 / model.scale) as f32)
+                if app.mouse.buttons.left().is_down() || app.mouse.buttons.right().is_down() {
+                    if let (Some(index), Some(start)) = (model.selected, model.drag_start) {
+                        model.drag_offset += vec2((delta.0 / model.scale) as f32, -(delta.1 / model.scale) as f32);
+                        let mut offset = model.drag_offset;
+                        // Shift keeps the node on the horizontal or vertical line through where it started
+                        if app.keys.mods.shift() {
+                            if offset.x.abs() > offset.y.abs() {
+                                offset.y = 0.0;
+                            } else {
+                                offset.x = 0.0;
+                            }
+                        }
+                        let mut pos = start + offset;
+                        if model.render_options.snap_to_grid {
+                            pos = (pos / GRID_SIZE).round() * GRID_SIZE;
+                            if app.keys.mods.shift() {
+                                // Snapping must not move the node off the line
+                                if offset.x == 0.0 { pos.x = start.x } else { pos.y = start.y }
+                            }
+                        }
+                        if pos != model.tree[index].pos {
+                            drag_node(model, index, pos);
+                        }
                     }
                 }
             }
             if let DeviceEvent::MouseWheel { delta: MouseScrollDelta::LineDelta(_x, y) } = data {
-                model.scale += -1.0 * f64::max(-1.0, f64::min(1.0, *y as f64)) * 0.5 * model.scale;
+                model.scale -= (*y as f64).clamp(-1.0, 1.0) * 0.5 * model.scale;
             }
             if let DeviceEvent::MouseWheel { delta: MouseScrollDelta::PixelDelta(PhysicalPosition{x,y})} = data {
                 if app.keys.mods.shift() {
-                    model.scale += -1.0 * f64::max(-1.0, f64::min(1.0, *y)) * 0.1 * model.scale;
+                    model.scale -= y.clamp(-1.0, 1.0) * 0.1 * model.scale;
                 } else {
                     model.camera.x += (x / model.scale / 2.0) as f32;
                     model.camera.y -= (y / model.scale / 2.0) as f32;
                 }
             }
         }
-        Event::Update(_) => {}
+        Event::Update(update) => {
+            update_cache(model);
+            finish_export(model);
+            update_animation(model, update.since_last.as_secs_f32());
+            if let Some((path, frame)) = model.pending_capture.take() {
+                if app.elapsed_frames() > frame {
+                    model.show_status(format!("Captured {}", path.display()));
+                } else {
+                    model.pending_capture = Some((path, frame));
+                }
+            }
+            if let Some((_, shown)) = &mut model.status {
+                *shown += update.since_last.as_secs_f32();
+                if *shown > STATUS_TIME {
+                    model.status = None;
+                }
+            }
+        }
         Event::Suspended => {}
         Event::Resumed => {}
     }
 }
 
+/// Collects finished searches and starts new ones when something the results depend on changed.
+fn update_cache(model: &mut Model) {
+    let tree = &model.tree;
+    let options = &model.render_options;
+    let cache = &mut model.cache;
+    // A waiting export needs the greedy cycle even when it isn't shown
+    let want_cycle = options.show_path || model.pending_export.is_some();
+    let key = Some((tree.generation(), model.cycle_len, want_cycle, options.show_exact, model.selected));
+    let tree_changed = cache.key.map(|key| key.0) != Some(tree.generation());
+    let cycle_changed = tree_changed || cache.key.map(|key| (key.1, key.2)) != Some((model.cycle_len, want_cycle));
+    let exact_changed = tree_changed || cache.key.map(|key| key.3) != Some(options.show_exact);
+    // Results of searches on an older tree would point at the wrong nodes
+    if cycle_changed {
+        cache.greedy.cancel();
+    }
+    if exact_changed {
+        cache.exact_solver.cancel();
+    }
+
+    let mut cycle_done = false;
+    for update in cache.greedy.poll() {
+        match update {
+            Update::Progress(length) => cache.greedy_progress = Some(length),
+            Update::Done(result) => {
+                cache.cycle_violations = match &result {
+                    Ok(cycle) => tree.validate_greedy_cycle(cycle),
+                    Err(_) => vec![],
+                };
+                cache.cycle = Some(result);
+                cache.greedy_progress = None;
+                cycle_done = true;
+            }
+        }
+    }
+    for update in cache.exact_solver.poll() {
+        match update {
+            Update::Progress(length) => cache.exact_progress = Some(length),
+            Update::Done(result) => {
+                cache.exact_violations = match &result {
+                    Ok(cycle) => tree.validate_hamiltonian_cycle(cycle),
+                    Err(_) => vec![],
+                };
+                cache.exact = Some(result);
+                cache.exact_progress = None;
+            }
+        }
+    }
+
+    if cache.key == key && !cycle_done {
+        return;
+    }
+
+    if tree_changed {
+        cache.special = tree.find_special_nodes();
+        cache.embedding = tree.embedding_problems().to_vec();
+    }
+    // Starting a new search cancels the old one, so dragging a node never waits for a stale result
+    if cycle_changed {
+        cache.cycle = None;
+        cache.cycle_violations = vec![];
+        cache.greedy_progress = None;
+        if want_cycle {
+            cache.greedy.submit(tree.clone(), model.cycle_len);
+        }
+    }
+    if exact_changed {
+        cache.exact = None;
+        cache.exact_violations = vec![];
+        cache.exact_progress = None;
+        if options.show_exact {
+            cache.exact_solver.submit(tree.clone(), 0);
+        }
+    }
+    cache.selected_vis = match (model.selected, &cache.cycle) {
+        (Some(index), Some(Ok(cycle))) => tree.check_node_vis_cycle(index, cycle),
+        (Some(_), Some(Err(_))) => vec![],
+        (Some(index), None) => tree.visibility().visible_from(index),
+        (None, _) => vec![],
+    };
+    cache.key = key;
+}
+
+/// Splits the tree edge closest to `pos` with a new node at `pos` and returns where it ended up.
+/// Nothing is placed when no edge is close enough or the node would break the embedding.
+fn place_node(model: &mut Model, mut pos: Point2) -> Option<TreeIndex> {
+    if model.render_options.snap_to_grid {
+        pos = (pos / GRID_SIZE).round() * GRID_SIZE;
+    }
+    let below = model.tree.nearest_tree_edge(pos, PLACE_DISTANCE / model.scale as f32)?;
+    if model.tree.insert_adds_problems(below, pos) {
+        model.show_status(format!("A node at {}, {} would break the embedding", pos.x, pos.y));
+        return None;
+    }
+    model.tree.insert_node(below.1, below.0, Node::from_pos(pos.x, pos.y));
+    model.history.push(Edit::Insert { index: below, node: model.tree[below], children: model.tree.children(below) });
+    Some(below)
+}
+
+fn drag_node(model: &mut Model, index: TreeIndex, pos: Point2) {
+    if model.render_options.reject_bad_drags {
+        model.tree.try_move_node(index, pos);
+    } else {
+        model.tree.move_node(index, pos);
+    }
+}
+
+/// Writes the svg or TikZ file of a waiting export once the greedy search is done,
+/// without a cycle when the search found none.
+fn finish_export(model: &mut Model) {
+    let (Some(kind), Some(result)) = (model.pending_export, &model.cache.cycle) else {
+        return;
+    };
+    let cycle = result.as_ref().ok().cloned();
+    model.pending_export = None;
+    let (path, result) = if kind == 'v' {
+        let path = model.file.with_extension("svg");
+        let result = model.tree.save_svg(&path, cycle.as_deref());
+        (path, result)
+    } else {
+        let path = model.file.with_extension("tex");
+        let result = model.tree.save_tikz(&path, cycle.as_deref(), DEFAULT_SIZE);
+        (path, result)
+    };
+    match (result, cycle) {
+        (Ok(()), Some(_)) => model.show_status(format!("Exported to {}", path.display())),
+        (Ok(()), None) => model.show_status(format!("Exported to {} without a cycle", path.display())),
+        (Err(err), _) => model.show_status(format!("Could not export {}: {}", path.display(), err)),
+    }
+}
+
+/// Plays the animation and stops it when the tree it was recorded on changed.
+fn update_animation(model: &mut Model, seconds: f32) {
+    if model.animation.as_ref().is_some_and(|animation| animation.generation != model.tree.generation()) {
+        model.animation = None;
+    }
+    if let Some(animation) = &mut model.animation {
+        animation.receive();
+        if !animation.playing {
+            return;
+        }
+        animation.elapsed += seconds;
+        while animation.elapsed > STEP_TIME {
+            animation.elapsed -= STEP_TIME;
+            animation.step(true);
+        }
+        if animation.position == animation.steps.len() && animation.recording.is_none() {
+            animation.playing = false;
+        }
+    }
+}
+
+/// Reads "x, y" or "x y".
+fn parse_coordinates(text: &str) -> Option<Point2> {
+    let numbers: Vec<f32> = text.split([',', ' ']).filter(|part| !part.is_empty()).map(|part| part.parse().ok()).collect::<Option<_>>()?;
+    match numbers[..] {
+        [x, y] => Some(Point2::new(x, y)),
+        _ => None,
+    }
+}
+
+/// The position of the mouse in tree coordinates.
+fn cursor_pos(app: &App, model: &Model) -> Point2 {
+    Point2::new(
+        app.mouse.position().x / model.scale as f32,
+        app.mouse.position().y / model.scale as f32)
+        - model.camera
+}
+
 pub fn render_triangle(app: &App, model: &Model) {
     let tree = &model.tree;
     let cam_pos = model.camera;
     let scale = model.scale as f32;
     let draw = app.draw();
     draw.background().color(BLACK);
+    if model.render_options.snap_to_grid {
+        draw_grid(app, &draw, model);
+    }
 
     //Draw triangle bounds
-    draw.line().end((tree.tree1.last().unwrap().pos + cam_pos) * scale).start((tree.tree2.last().unwrap().pos + cam_pos) * scale).color(GRAY);
-    draw.line().end((tree.tree2.last().unwrap().pos + cam_pos) * scale).start((tree.tree3.last().unwrap().pos + cam_pos) * scale).color(GRAY);
-    draw.line().end((tree.tree3.last().unwrap().pos + cam_pos) * scale).start((tree.tree1.last().unwrap().pos + cam_pos) * scale).color(GRAY);
+    let corners = tree.start_cycle();
+    for (prev, next) in (1..corners.len()).zip((1..corners.len()).cycle().skip(1)) {
+        draw.line().end((tree[corners[prev]].pos + cam_pos) * scale).start((tree[corners[next]].pos + cam_pos) * scale).color(GRAY);
+    }
 
     //Draw edges
     for e in tree.get_all_edges() {
@@ -134,32 +677,120 @@ pub fn render_triangle(app: &App, model: &Model) {
 
     //Draw all the nodes
     for index in tree.iter() {
-            draw.ellipse().xy((tree[index].pos + cam_pos) * scale).color(
-                match index.0 {
-                    TreesEnum::First => { BLUE }
-                    TreesEnum::Second => { GREEN }
-                    TreesEnum::Third => { RED }
-                    TreesEnum::Center => { GRAY }
-                }
-            ).radius(SIZE);
+        let color = match index.0 {
+            TreesEnum::Branch(i) => { branch_color(i).1 }
+            TreesEnum::Center => { GRAY }
+        };
+        // Steiner points are only drawn as a small ring, cycles don't visit them
+        if tree[index].steiner {
+            draw.ellipse().xy((tree[index].pos + cam_pos) * scale).no_fill().stroke(color).stroke_weight(2.0).radius(SIZE / 2.0);
+        } else {
+            draw.ellipse().xy((tree[index].pos + cam_pos) * scale).color(color).radius(SIZE);
+        }
+    }
+    let mut errors = vec![];
+    match &model.cache.cycle {
+        Some(Ok(output)) => {
+            let violations = &model.cache.cycle_violations;
+            if !violations.is_empty() {
+                errors.push((format!("greedy: {} violations", violations.len()), PINK));
+            }
+            draw_cycle(&draw, model, output.clone(), PINK);
+            draw_violations(&draw, model, output, violations);
+        }
+        Some(Err(err)) => errors.push((format!("greedy: {}", err), PINK)),
+        None => if model.cache.greedy.is_running() {
+            errors.push((format!("greedy: searching, length {}", model.cache.greedy_progress.unwrap_or(0)), PINK));
+        }
     }
-    if model.render_options.show_path {
-        if let Ok(output) = tree.find_cycle(model.cycle_len) {
-            draw_cycle(&draw, model, output, PINK);
+    match &model.cache.exact {
+        Some(Ok(output)) => {
+            let violations = &model.cache.exact_violations;
+            if !violations.is_empty() {
+                errors.push((format!("exact: {} violations", violations.len()), ORANGE));
+            }
+            draw_cycle(&draw, model, output.clone(), ORANGE);
+            draw_violations(&draw, model, output, violations);
+        }
+        Some(Err(err)) => errors.push((format!("exact: {}", err), ORANGE)),
+        None => if model.cache.exact_solver.is_running() {
+            errors.push((format!("exact: searching, longest path {}", model.cache.exact_progress.unwrap_or(0)), ORANGE));
         }
     }
+    if !model.cache.embedding.is_empty() {
+        errors.push((format!("tree: {} embedding problems", model.cache.embedding.len()), RED));
+        draw_embedding_problems(&draw, model);
+    }
+    if let Some(animation) = &model.animation {
+        errors.push(draw_animation(&draw, model, animation));
+    }
+    if let Some((index, text)) = &model.coordinate_entry {
+        errors.push((format!("coordinates of {:?} (x, y), enter to apply, escape to cancel: {}_", index, text), WHITE));
+        draw.ellipse().xy((tree[*index].pos + cam_pos) * scale).no_fill().stroke(WHITE).stroke_weight(2.0).radius(SIZE * 2.0);
+    }
+    if let (Some((text, _)), None) = (&model.status, &model.pending_capture) {
+        errors.push((text.clone(), WHITE));
+    }
+    draw_messages(app, &draw, errors);
     if let Some(index) = model.selected {
         draw_vis_edges(&draw, model, index);
         draw.ellipse().xy((tree[index].pos + cam_pos) * scale).color(WHITE).radius(SIZE);
     };
 
-    draw_node_list(&draw, model, tree.find_special_nodes(), DARKCYAN);
+    draw_node_list(&draw, model, model.cache.special.clone(), DARKCYAN);
+}
+
+/// Draws the cycle as far as the animation got and highlights the last step, returns a description of the step.
+fn draw_animation(draw: &Draw, model: &Model, animation: &Animation) -> (String, Srgb<u8>) {
+    let tree = &model.tree;
+    let cam_pos = model.camera;
+    let scale = model.scale as f32;
+    if animation.steps.len() < animation.position {
+        return ("recording the greedy search".to_string(), LIGHTSKYBLUE);
+    }
+    let mut cycle = vec![];
+    let mut strict = true;
+    for step in &animation.steps[..animation.position] {
+        match step {
+            Step::Start(start) => cycle = start.clone(),
+            Step::Insert { node, position, .. } => cycle.insert(*position, *node),
+            Step::Relaxed => strict = false,
+            Step::Candidate { .. } | Step::Finished(_) => {}
+        }
+    }
+    draw_cycle(draw, model, cycle, LIGHTSKYBLUE);
+
+    let highlight = |edge: (TreeIndex, TreeIndex), node: TreeIndex, color: Srgb<u8>| {
+        draw.line().end((tree[edge.0].pos + cam_pos) * scale).start((tree[edge.1].pos + cam_pos) * scale).weight(3.0).color(WHITE);
+        draw.ellipse().xy((tree[node].pos + cam_pos) * scale).no_fill().stroke(color).stroke_weight(2.0).radius(SIZE * 2.0);
+    };
+    let pass = if strict { "strict" } else { "relaxed" };
+    let description = match &animation.steps[animation.position - 1] {
+        Step::Start(start) => format!("start with {:?}", start),
+        Step::Candidate { edge, node } => {
+            highlight(*edge, *node, YELLOW);
+            format!("{:?} is not visible from {:?}", node, edge.1)
+        }
+        Step::Insert { edge, node, .. } => {
+            highlight(*edge, *node, LIME);
+            format!("insert {:?} between {:?} and {:?}", node, edge.0, edge.1)
+        }
+        Step::Relaxed => "the strict pass is stuck, allowing one odd node".to_string(),
+        Step::Finished(Ok(_)) => "found the cycle".to_string(),
+        Step::Finished(Err(err)) => err.to_string(),
+    };
+    let state = if animation.playing { "playing" } else { "paused" };
+    let recording = if animation.recording.is_some() { "+" } else { "" };
+    (format!("step {}/{}{} ({} pass, {}): {}", animation.position, animation.steps.len(), recording, pass, state, description), LIGHTSKYBLUE)
 }
 
 fn draw_cycle(draw: &Draw, model: &Model, list: Vec<TreeIndex>, color: Srgb<u8>) {
     let tree = &model.tree;
     let cam_pos = model.camera;
     let scale = model.scale as f32;
+    if list.is_empty() {
+        return;
+    }
     for i in 0..list.len() - 1 {
         // Print the current element and the next element
         draw.text(&i.to_string()).xy((tree[list[i]].pos + cam_pos) * scale);
@@ -169,6 +800,87 @@ fn draw_cycle(draw: &Draw, model: &Model, list: Vec<TreeIndex>, color: Srgb<u8>)
     // draw_node_list(draw, model, list, color);
 }
 
+fn draw_violations(draw: &Draw, model: &Model, cycle: &[TreeIndex], violations: &[Violation]) {
+    let tree = &model.tree;
+    let cam_pos = model.camera;
+    let scale = model.scale as f32;
+    let cycle_edges = tree.get_cycle_edges(cycle);
+    let draw_edge = |e: Edge| {
+        draw.line().end((e.0 + cam_pos) * scale).start((e.1 + cam_pos) * scale).weight(3.0).color(RED);
+    };
+    for violation in violations {
+        match violation {
+            Violation::TooShort(_) => {}
+            Violation::DuplicateNode(index) | Violation::MissingNode(index) | Violation::SteinerNode(index) => {
+                draw.ellipse().xy((tree[*index].pos + cam_pos) * scale).no_fill().stroke(RED).stroke_weight(2.0).radius(SIZE * 2.0);
+            }
+            Violation::TreeCrossing { cycle_edge, tree_edge } => {
+                draw_edge(cycle_edges[*cycle_edge]);
+                draw_edge(*tree_edge);
+            }
+            Violation::CycleCrossing(a, b) => {
+                draw_edge(cycle_edges[*a]);
+                draw_edge(cycle_edges[*b]);
+            }
+            Violation::ColourJump(a, b) => {
+                draw_edge(Edge(tree[*a].pos, tree[*b].pos));
+            }
+        }
+    }
+}
+
+fn draw_grid(app: &App, draw: &Draw, model: &Model) {
+    let scale = model.scale as f32;
+    if GRID_SIZE * scale < 4.0 {
+        return;
+    }
+    let win = app.window_rect();
+    // The window in tree coordinates
+    let low = Point2::new(win.left(), win.bottom()) / scale - model.camera;
+    let high = Point2::new(win.right(), win.top()) / scale - model.camera;
+    let color = rgb8(40, 40, 40);
+    let mut x = (low.x / GRID_SIZE).floor() * GRID_SIZE;
+    while x <= high.x {
+        let screen = (x + model.camera.x) * scale;
+        draw.line().start(pt2(screen, win.bottom())).end(pt2(screen, win.top())).color(color);
+        x += GRID_SIZE;
+    }
+    let mut y = (low.y / GRID_SIZE).floor() * GRID_SIZE;
+    while y <= high.y {
+        let screen = (y + model.camera.y) * scale;
+        draw.line().start(pt2(win.left(), screen)).end(pt2(win.right(), screen)).color(color);
+        y += GRID_SIZE;
+    }
+}
+
+fn draw_embedding_problems(draw: &Draw, model: &Model) {
+    let tree = &model.tree;
+    let cam_pos = model.camera;
+    let scale = model.scale as f32;
+    for problem in &model.cache.embedding {
+        if let EmbeddingProblem::CrossingEdges(a, b) = problem {
+            for e in [a, b] {
+                draw.line().end((e.0 + cam_pos) * scale).start((e.1 + cam_pos) * scale).weight(3.0).color(RED);
+            }
+        }
+        for index in problem.nodes() {
+            draw.ellipse().xy((tree[index].pos + cam_pos) * scale).no_fill().stroke(RED).stroke_weight(2.0).radius(SIZE * 2.0);
+        }
+    }
+}
+
+fn draw_messages(app: &App, draw: &Draw, messages: Vec<(String, Srgb<u8>)>) {
+    let win = app.window_rect();
+    for (i, (message, color)) in messages.iter().enumerate() {
+        draw.text(message)
+            .left_justify()
+            .w(win.w() - 20.0)
+            .x(0.0)
+            .y(win.top() - 20.0 - i as f32 * 20.0)
+            .color(*color);
+    }
+}
+
 fn draw_node_list(draw: &Draw, model: &Model, list: Vec<TreeIndex>, color: Srgb<u8>) {
     let tree = &model.tree;
     let cam_pos = model.camera;
@@ -184,17 +896,8 @@ fn draw_vis_edges(draw: &Draw, model: &Model, index: TreeIndex) {
     let tree = &model.tree;
     let cam_pos = model.camera;
     let scale = model.scale as f32;
-    let edges = if model.render_options.show_path {
-        if let Ok(cycle) = tree.find_cycle(model.cycle_len) {
-            tree.check_node_vis_cycle(index, &cycle)
-        } else {
-            vec![]
-        }
-    } else {
-        tree.check_node_vis(index)
-    };
-    for edge in edges {
-        draw.line().end((tree[index].pos + cam_pos) * scale).start((tree[edge].pos + cam_pos) * scale).color(PURPLE);
+    for edge in &model.cache.selected_vis {
+        draw.line().end((tree[index].pos + cam_pos) * scale).start((tree[*edge].pos + cam_pos) * scale).color(PURPLE);
     }
 }
 
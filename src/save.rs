@@ -0,0 +1,117 @@
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Error, ErrorKind};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::node::Node;
+use crate::tree::Tree;
+use crate::tree_enum::TreesEnum;
+use crate::tree_index::TreeIndex;
+
+/// The on disk representation of a `Tree`, every node is stored as an `[x, y]` pair.
+/// Files from before trees could have any amount of branches store them in `tree1`, `tree2` and `tree3`,
+/// files without `parents` have branches that are simple paths from the center to the corner,
+/// and files without `steiner` have no Steiner points.
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SavedTree {
+    pub center: [f32; 2],
+    #[serde(default)]
+    pub branches: Vec<Vec<[f32; 2]>>,
+    #[serde(default)]
+    pub parents: Vec<Vec<Option<usize>>>,
+    #[serde(default)]
+    pub steiner: Vec<Vec<bool>>,
+    #[serde(default, skip_serializing)]
+    pub tree1: Vec<[f32; 2]>,
+    #[serde(default, skip_serializing)]
+    pub tree2: Vec<[f32; 2]>,
+    #[serde(default, skip_serializing)]
+    pub tree3: Vec<[f32; 2]>,
+}
+
+impl SavedTree {
+    pub fn from_tree(tree: &Tree) -> Self {
+        let positions = |branch: &Vec<Node>| branch.iter().map(|node| node.pos.to_array()).collect();
+        Self {
+            center: tree.center().pos.to_array(),
+            branches: tree.branches().iter().map(positions).collect(),
+            parents: tree.branches().iter().map(|branch| branch.iter().map(|node| node.parent).collect()).collect(),
+            steiner: tree.branches().iter().map(|branch| branch.iter().map(|node| node.steiner).collect()).collect(),
+            tree1: vec![],
+            tree2: vec![],
+            tree3: vec![],
+        }
+    }
+
+    pub fn to_tree(&self) -> Result<Tree, Error> {
+        let branches = if self.branches.is_empty() {
+            vec![&self.tree1, &self.tree2, &self.tree3]
+        } else {
+            self.branches.iter().collect()
+        };
+        if branches.len() < 3 {
+            return Err(Error::new(ErrorKind::InvalidData, "a tree needs at least 3 branches"));
+        }
+        let mut tree = Tree::empty(branches.len());
+        tree[TreeIndex(TreesEnum::Center, 0)] = Node::from_pos(self.center[0], self.center[1]);
+        for (i, positions) in branches.into_iter().enumerate() {
+            if positions.is_empty() {
+                return Err(Error::new(ErrorKind::InvalidData, format!("branch {} has no nodes", i)));
+            }
+            for pos in positions {
+                tree.add_node(TreesEnum::Branch(i), Node::from_pos(pos[0], pos[1]));
+            }
+            if let Some(parents) = self.parents.get(i) {
+                if parents.len() != positions.len() {
+                    return Err(Error::new(ErrorKind::InvalidData, format!("branch {} has the wrong amount of parents", i)));
+                }
+                for (node, parent) in tree[TreesEnum::Branch(i)].iter_mut().zip(parents) {
+                    node.parent = *parent;
+                }
+                if !is_rooted(&tree[TreesEnum::Branch(i)]) {
+                    return Err(Error::new(ErrorKind::InvalidData, format!("branch {} is not connected to the center", i)));
+                }
+            }
+            if let Some(steiner) = self.steiner.get(i) {
+                if steiner.len() != positions.len() {
+                    return Err(Error::new(ErrorKind::InvalidData, format!("branch {} has the wrong amount of Steiner flags", i)));
+                }
+                if steiner.last() == Some(&true) {
+                    return Err(Error::new(ErrorKind::InvalidData, format!("the corner of branch {} can't be a Steiner point", i)));
+                }
+                for (node, steiner) in tree[TreesEnum::Branch(i)].iter_mut().zip(steiner) {
+                    node.steiner = *steiner;
+                }
+            }
+        }
+        Ok(tree)
+    }
+}
+
+impl Tree {
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        let writer = BufWriter::new(File::create(path)?);
+        serde_json::to_writer_pretty(writer, &SavedTree::from_tree(self))?;
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Tree, Error> {
+        let reader = BufReader::new(File::open(path)?);
+        let saved: SavedTree = serde_json::from_reader(reader)?;
+        saved.to_tree()
+    }
+}
+
+/// Checks that following the parents from any node of the branch ends up at the center.
+fn is_rooted(branch: &[Node]) -> bool {
+    branch.iter().all(|node| {
+        let mut parent = node.parent;
+        for _ in 0..branch.len() {
+            match parent {
+                Some(i) if i < branch.len() => parent = branch[i].parent,
+                Some(_) => return false,
+                None => return true,
+            }
+        }
+        false
+    })
+}
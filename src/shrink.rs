@@ -0,0 +1,79 @@
+use std::cmp::Reverse;
+use std::mem::discriminant;
+use crate::cycle_error::CycleError;
+use crate::tree::Tree;
+use crate::tree_index::TreeIndex;
+
+/// The error of the greedy `find_cycle` for a cycle through every node except the Steiner points, `None` if it finds one.
+pub fn greedy_error(tree: &Tree) -> Option<CycleError> {
+    tree.find_cycle(tree.full_cycle_len()).err()
+}
+
+/// Checks if the greedy search fails on a tree with the same kind of error as `error`,
+/// so shrinking doesn't trade the original failure for a different one.
+pub fn greedy_fails_like(error: &CycleError) -> impl Fn(&Tree) -> bool {
+    let kind = discriminant(error);
+    move |tree| greedy_error(tree).is_some_and(|other| discriminant(&other) == kind)
+}
+
+/// Delta debugging: keeps removing chunks of branch nodes while `fails` still holds,
+/// halving the chunk size whenever no chunk can be removed.
+/// The corners are never removed and the tree edges never start crossing.
+pub fn shrink<F: Fn(&Tree) -> bool>(tree: &Tree, fails: F) -> Tree {
+    let mut tree = tree.clone();
+    let mut chunk_size = (tree.removable_nodes().len() / 2).max(1);
+    loop {
+        let removable = tree.removable_nodes();
+        if removable.is_empty() {
+            break;
+        }
+        let smaller = removable.chunks(chunk_size.min(removable.len()))
+            .map(|chunk| tree.without_nodes(chunk))
+            .find(|candidate| !candidate.tree_edges_cross() && fails(candidate));
+        match smaller {
+            Some(candidate) => tree = candidate,
+            None if chunk_size == 1 => break,
+            None => chunk_size /= 2,
+        }
+    }
+    tree
+}
+
+impl Tree {
+    /// All nodes that can be removed without removing a corner of the polygon.
+    fn removable_nodes(&self) -> Vec<TreeIndex> {
+        let mut output = vec![];
+        for branch in self.branch_ids() {
+            for i in 0..self[branch].len().saturating_sub(1) {
+                output.push(TreeIndex(branch, i));
+            }
+        }
+        output
+    }
+
+    fn without_nodes(&self, nodes: &[TreeIndex]) -> Tree {
+        let mut output = self.clone();
+        let mut nodes = nodes.to_vec();
+        // Remove from the back so the other indices stay valid
+        nodes.sort_by_key(|node| Reverse(node.1));
+        for node in nodes {
+            output.take_node(node);
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::mem::discriminant;
+    use super::*;
+
+    #[test]
+    fn shrinking_keeps_the_kind_of_failure() {
+        let tree = Tree::random(&[5, 5, 5], 536);
+        let error = greedy_error(&tree).unwrap();
+        let smallest = shrink(&tree, greedy_fails_like(&error));
+        assert!(smallest.iter().len() < tree.iter().len());
+        assert_eq!(discriminant(&greedy_error(&smallest).unwrap()), discriminant(&error));
+    }
+}
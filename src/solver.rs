@@ -0,0 +1,109 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use crate::cycle_error::CycleError;
+use crate::tree::Tree;
+use crate::tree_index::TreeIndex;
+
+/// A cycle search that can be run by a `Solver`, gets the tree, the cycle length, a cancel flag and a progress callback.
+pub type Search = fn(&Tree, usize, &AtomicBool, &mut dyn FnMut(usize)) -> Result<Vec<TreeIndex>, CycleError>;
+
+pub enum Update {
+    /// The length of the longest cycle or path found so far
+    Progress(usize),
+    Done(Result<Vec<TreeIndex>, CycleError>),
+}
+
+struct Job {
+    id: u64,
+    tree: Tree,
+    length: usize,
+    cancel: Arc<AtomicBool>,
+}
+
+/// Runs a cycle search on a worker thread, starting a new search cancels the one still running.
+pub struct Solver {
+    jobs: Sender<Job>,
+    updates: Receiver<(u64, Update)>,
+    current: Option<(u64, Arc<AtomicBool>)>,
+    next_id: u64,
+}
+
+impl Solver {
+    pub fn spawn(search: Search) -> Self {
+        let (jobs, job_receiver) = channel::<Job>();
+        let (update_sender, updates) = channel();
+        thread::spawn(move || {
+            // Stops when the solver is dropped and the channel closes
+            for job in job_receiver {
+                if job.cancel.load(Ordering::Relaxed) {
+                    continue;
+                }
+                let mut progress = |length| {
+                    let _ = update_sender.send((job.id, Update::Progress(length)));
+                };
+                let result = search(&job.tree, job.length, &job.cancel, &mut progress);
+                let _ = update_sender.send((job.id, Update::Done(result)));
+            }
+        });
+        Self {
+            jobs,
+            updates,
+            current: None,
+            next_id: 0,
+        }
+    }
+
+    /// The greedy `find_cycle`.
+    pub fn greedy() -> Self {
+        Self::spawn(|tree, length, cancel, progress| tree.find_cycle_cancellable(length, cancel, progress))
+    }
+
+    /// The exhaustive `find_cycle_exact`, which ignores the length.
+    pub fn exact() -> Self {
+        Self::spawn(|tree, _, cancel, progress| tree.find_cycle_exact_cancellable(cancel, progress))
+    }
+
+    /// Starts a search on a snapshot of the tree.
+    pub fn submit(&mut self, tree: Tree, length: usize) {
+        self.cancel();
+        let cancel = Arc::new(AtomicBool::new(false));
+        let id = self.next_id;
+        self.next_id += 1;
+        self.current = Some((id, cancel.clone()));
+        let _ = self.jobs.send(Job { id, tree, length, cancel });
+    }
+
+    /// Stops the running search, its results are never reported.
+    pub fn cancel(&mut self) {
+        if let Some((_, cancel)) = self.current.take() {
+            cancel.store(true, Ordering::Relaxed);
+        }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.current.is_some()
+    }
+
+    /// The updates of the current search since the last call, without blocking.
+    pub fn poll(&mut self) -> Vec<Update> {
+        let mut output = vec![];
+        while let Ok((id, update)) = self.updates.try_recv() {
+            if self.current.as_ref().map(|(current, _)| *current) != Some(id) {
+                continue;
+            }
+            if let Update::Done(_) = update {
+                self.current = None;
+            }
+            output.push(update);
+        }
+        output
+    }
+}
+
+impl Drop for Solver {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
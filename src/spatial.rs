@@ -0,0 +1,254 @@
+use nannou::geom::Point2;
+use crate::edge::Edge;
+
+/// A uniform grid over a set of edges, every cell stores the edges that pass through it or one of its neighbours.
+/// Segment queries only look at the cells the segment passes through.
+#[derive(Clone, Debug)]
+pub struct EdgeGrid {
+    edges: Vec<Edge>,
+    shape: Shape,
+    cells: Vec<Vec<usize>>,
+}
+
+impl EdgeGrid {
+    pub fn new(edges: Vec<Edge>) -> Self {
+        let shape = Shape::new(edges.iter().flat_map(|edge| [edge.0, edge.1]), edges.len());
+        let mut grid = Self {
+            edges,
+            cells: vec![vec![]; shape.columns * shape.rows],
+            shape,
+        };
+        for i in 0..grid.edges.len() {
+            let edge = grid.edges[i];
+            let mut cells = std::mem::take(&mut grid.cells);
+            // The neighbours too, so a query that meets the edge on a cell border finds it from both sides
+            let walked = grid.walk(edge, |x, y| {
+                for y in y.saturating_sub(1)..(y + 2).min(grid.shape.rows) {
+                    for x in x.saturating_sub(1)..(x + 2).min(grid.shape.columns) {
+                        let cell = &mut cells[y * grid.shape.columns + x];
+                        if cell.last() != Some(&i) {
+                            cell.push(i);
+                        }
+                    }
+                }
+                false
+            });
+            if walked.is_none() {
+                // Rounding made the walk miss the last cell, use every cell of the bounding box
+                let (low_x, low_y) = grid.shape.cell_of(edge.0.min(edge.1));
+                let (high_x, high_y) = grid.shape.cell_of(edge.0.max(edge.1));
+                for y in low_y.saturating_sub(1)..(high_y + 2).min(grid.shape.rows) {
+                    for x in low_x.saturating_sub(1)..(high_x + 2).min(grid.shape.columns) {
+                        let cell = &mut cells[y * grid.shape.columns + x];
+                        if !cell.contains(&i) {
+                            cell.push(i);
+                        }
+                    }
+                }
+            }
+            grid.cells = cells;
+        }
+        grid
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    /// Calls `f` on every edge that might touch `segment` and returns true as soon as `f` does.
+    /// Edges can be passed more than once.
+    pub fn any(&self, segment: Edge, mut f: impl FnMut(&Edge) -> bool) -> bool {
+        self.any_index(segment, |i| f(&self.edges[i]))
+    }
+
+    /// Like `any`, but passes the position of the edge in `edges`.
+    pub fn any_index(&self, segment: Edge, mut f: impl FnMut(usize) -> bool) -> bool {
+        let walked = self.walk(segment, |x, y| self.cells[y * self.shape.columns + x].iter().any(|i| f(*i)));
+        // Rounding made the walk miss the last cell, fall back to checking everything
+        walked.unwrap_or_else(|| (0..self.edges.len()).any(f))
+    }
+
+    /// Calls `f` on the cells along `segment` and returns true as soon as `f` does.
+    /// `None` if rounding made the walk miss the last cell.
+    fn walk(&self, segment: Edge, mut f: impl FnMut(usize, usize) -> bool) -> Option<bool> {
+        let Some((start, end)) = self.clip(segment) else {
+            return Some(false);
+        };
+        let mut check = |x: i64, y: i64| {
+            if x < 0 || y < 0 || x as usize >= self.shape.columns || y as usize >= self.shape.rows {
+                return false;
+            }
+            f(x as usize, y as usize)
+        };
+
+        // Walk the cells along the segment (Amanatides & Woo)
+        let a = (start - self.shape.origin) / self.shape.cell_size;
+        let b = (end - self.shape.origin) / self.shape.cell_size;
+        let (cell_a, cell_b) = (self.shape.cell_of(start), self.shape.cell_of(end));
+        let (mut x, mut y) = (cell_a.0 as i64, cell_a.1 as i64);
+        let (end_x, end_y) = (cell_b.0 as i64, cell_b.1 as i64);
+        let d = b - a;
+        let step_x = if d.x > 0.0 { 1 } else { -1 };
+        let step_y = if d.y > 0.0 { 1 } else { -1 };
+        let delta_x = if d.x != 0.0 { 1.0 / d.x.abs() } else { f32::INFINITY };
+        let delta_y = if d.y != 0.0 { 1.0 / d.y.abs() } else { f32::INFINITY };
+        let mut max_x = if d.x > 0.0 { (x as f32 + 1.0 - a.x) * delta_x } else { (a.x - x as f32) * delta_x };
+        let mut max_y = if d.y > 0.0 { (y as f32 + 1.0 - a.y) * delta_y } else { (a.y - y as f32) * delta_y };
+        if d.x == 0.0 { max_x = f32::INFINITY; }
+        if d.y == 0.0 { max_y = f32::INFINITY; }
+
+        let limit = self.shape.columns + self.shape.rows + 2;
+        for _ in 0..limit {
+            if check(x, y) {
+                return Some(true);
+            }
+            if x == end_x && y == end_y {
+                return Some(false);
+            }
+            if (max_x - max_y).abs() < 1e-5 {
+                // Passing through a corner, also look at both cells next to it
+                if check(x + step_x, y) || check(x, y + step_y) {
+                    return Some(true);
+                }
+                x += step_x;
+                y += step_y;
+                max_x += delta_x;
+                max_y += delta_y;
+            } else if max_x < max_y {
+                x += step_x;
+                max_x += delta_x;
+            } else {
+                y += step_y;
+                max_y += delta_y;
+            }
+        }
+        None
+    }
+
+    /// Clips a segment to the bounds of the grid (Liang & Barsky).
+    fn clip(&self, segment: Edge) -> Option<(Point2, Point2)> {
+        let max = self.shape.origin + Point2::new(self.shape.columns as f32, self.shape.rows as f32) * self.shape.cell_size;
+        let d = segment.1 - segment.0;
+        let (mut t0, mut t1) = (0.0f32, 1.0f32);
+        for (p, q) in [
+            (-d.x, segment.0.x - self.shape.origin.x),
+            (d.x, max.x - segment.0.x),
+            (-d.y, segment.0.y - self.shape.origin.y),
+            (d.y, max.y - segment.0.y),
+        ] {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return None;
+                }
+            } else if p < 0.0 {
+                t0 = t0.max(q / p);
+            } else {
+                t1 = t1.min(q / p);
+            }
+        }
+        if t0 > t1 {
+            return None;
+        }
+        Some((segment.0 + d * t0, segment.0 + d * t1))
+    }
+}
+
+/// A uniform grid over a set of points, for finding the points in a region without looking at all of them.
+#[derive(Clone, Debug)]
+pub struct PointGrid {
+    shape: Shape,
+    cells: Vec<Vec<usize>>,
+}
+
+impl PointGrid {
+    pub fn new(points: &[Point2]) -> Self {
+        let shape = Shape::new(points.iter().copied(), points.len());
+        let mut cells = vec![vec![]; shape.columns * shape.rows];
+        for (i, point) in points.iter().enumerate() {
+            let (x, y) = shape.cell_of(*point);
+            cells[y * shape.columns + x].push(i);
+        }
+        Self { shape, cells }
+    }
+
+    /// Calls `f` with the position in `points` of every point in a cell that the triangle overlaps, each one once.
+    pub fn in_triangle(&self, triangle: [Point2; 3], mut f: impl FnMut(usize)) {
+        let low = triangle.iter().fold(f32::INFINITY, |low, p| low.min(p.y));
+        let high = triangle.iter().fold(f32::NEG_INFINITY, |high, p| high.max(p.y));
+        let (_, first_row) = self.shape.cell_of(Point2::new(self.shape.origin.x, low));
+        let (_, last_row) = self.shape.cell_of(Point2::new(self.shape.origin.x, high));
+        // Rows and columns are a bit wider than their cells, so rounding can't lose points on a border
+        let margin = self.shape.cell_size * 1e-3;
+        for row in first_row..=last_row {
+            // The part of the triangle in this row is convex, so its widest points are corners of the triangle
+            // or points where the sides of the triangle cross the border of the row
+            let bottom = (self.shape.origin.y + row as f32 * self.shape.cell_size - margin).max(low);
+            let top = (self.shape.origin.y + (row + 1) as f32 * self.shape.cell_size + margin).min(high);
+            if bottom > top {
+                continue;
+            }
+            let (mut left, mut right) = (f32::INFINITY, f32::NEG_INFINITY);
+            for (i, p) in triangle.iter().enumerate() {
+                if p.y >= bottom && p.y <= top {
+                    (left, right) = (left.min(p.x), right.max(p.x));
+                }
+                let q = triangle[(i + 1) % 3];
+                for y in [bottom, top] {
+                    if p.y != q.y && (p.y - y) * (q.y - y) <= 0.0 {
+                        let x = p.x + (y - p.y) / (q.y - p.y) * (q.x - p.x);
+                        (left, right) = (left.min(x), right.max(x));
+                    }
+                }
+            }
+            if left > right {
+                continue;
+            }
+            let (first_column, _) = self.shape.cell_of(Point2::new(left - margin, bottom));
+            let (last_column, _) = self.shape.cell_of(Point2::new(right + margin, bottom));
+            for column in first_column..=last_column {
+                self.cells[row * self.shape.columns + column].iter().for_each(|i| f(*i));
+            }
+        }
+    }
+}
+
+/// The cells of a grid, about as many as the items it is built for, over the bounds of some points.
+#[derive(Clone, Debug)]
+struct Shape {
+    origin: Point2,
+    cell_size: f32,
+    columns: usize,
+    rows: usize,
+}
+
+impl Shape {
+    fn new(points: impl Iterator<Item = Point2>, items: usize) -> Self {
+        let (mut min, mut max) = (Point2::splat(f32::INFINITY), Point2::splat(f32::NEG_INFINITY));
+        for point in points {
+            min = min.min(point);
+            max = max.max(point);
+        }
+        if items == 0 {
+            (min, max) = (Point2::ZERO, Point2::ZERO);
+        }
+        let side = (items as f32).sqrt().ceil().max(1.0);
+        let extent = (max - min).max_element().max(1.0);
+        // A little padding so the items on the border of the bounds end up inside the grid
+        let cell_size = extent * 1.001 / side;
+        let origin = min - Point2::splat(extent * 0.0005);
+        Self {
+            origin,
+            cell_size,
+            columns: ((max.x - origin.x) / cell_size).floor() as usize + 1,
+            rows: ((max.y - origin.y) / cell_size).floor() as usize + 1,
+        }
+    }
+
+    fn cell_of(&self, point: Point2) -> (usize, usize) {
+        let cell = (point - self.origin) / self.cell_size;
+        (
+            (cell.x.floor().max(0.0) as usize).min(self.columns - 1),
+            (cell.y.floor().max(0.0) as usize).min(self.rows - 1),
+        )
+    }
+}
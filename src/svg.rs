@@ -0,0 +1,70 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Error;
+use std::path::Path;
+use nannou::geom::Point2;
+use crate::drawing::{branch_color, NODE_RADIUS};
+use crate::tree::Tree;
+use crate::tree_enum::TreesEnum;
+use crate::tree_index::TreeIndex;
+
+const MARGIN: f32 = 20.0;
+
+impl Tree {
+    /// Draws the tree like the viewer does: bounds, tree edges, nodes, the cycle with its order and the special nodes.
+    pub fn to_svg(&self, cycle: Option<&[TreeIndex]>) -> String {
+        let (mut min, mut max) = self.bounds();
+        min -= Point2::splat(MARGIN);
+        max += Point2::splat(MARGIN);
+        let size = max - min;
+        // SVG has y pointing down
+        let at = |pos: Point2| (pos.x - min.x, max.y - pos.y);
+        let line = |out: &mut String, a: Point2, b: Point2, color: &str| {
+            let ((x1, y1), (x2, y2)) = (at(a), at(b));
+            let _ = writeln!(out, r#"  <line x1="{}" y1="{}" x2="{}" y2="{}" stroke="{}"/>"#, x1, y1, x2, y2, color);
+        };
+        let circle = |out: &mut String, index: TreeIndex, radius: f32, color: &str| {
+            let (x, y) = at(self[index].pos);
+            let _ = writeln!(out, r#"  <circle cx="{}" cy="{}" r="{}" fill="{}"/>"#, x, y, radius, color);
+        };
+
+        let mut out = String::new();
+        let _ = writeln!(out, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}">"#, size.x, size.y, size.x, size.y);
+        let _ = writeln!(out, r#"  <rect width="100%" height="100%" fill="black"/>"#);
+
+        let corners = self.start_cycle();
+        for (prev, next) in (1..corners.len()).zip((1..corners.len()).cycle().skip(1)) {
+            line(&mut out, self[corners[prev]].pos, self[corners[next]].pos, "gray");
+        }
+        for edge in self.get_all_edges() {
+            line(&mut out, edge.0, edge.1, "gray");
+        }
+        for index in self.iter() {
+            let color = match index.0 {
+                _ if self[index].steiner => "gray",
+                TreesEnum::Branch(i) => branch_color(i).0,
+                TreesEnum::Center => "gray",
+            };
+            let radius = if self[index].steiner { NODE_RADIUS / 2.0 } else { NODE_RADIUS };
+            circle(&mut out, index, radius, color);
+        }
+        if let Some(cycle) = cycle {
+            for (i, index) in cycle.iter().enumerate() {
+                line(&mut out, self[*index].pos, self[cycle[(i + 1) % cycle.len()]].pos, "pink");
+            }
+            for (i, index) in cycle.iter().enumerate() {
+                let (x, y) = at(self[*index].pos);
+                let _ = writeln!(out, r#"  <text x="{}" y="{}" fill="white" font-size="10" text-anchor="middle" dominant-baseline="central">{}</text>"#, x, y, i);
+            }
+        }
+        for index in self.find_special_nodes() {
+            circle(&mut out, index, NODE_RADIUS, "darkcyan");
+        }
+        out.push_str("</svg>\n");
+        out
+    }
+
+    pub fn save_svg(&self, path: &Path, cycle: Option<&[TreeIndex]>) -> Result<(), Error> {
+        fs::write(path, self.to_svg(cycle))
+    }
+}
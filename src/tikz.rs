@@ -0,0 +1,71 @@
+use std::fmt::Write as _;
+use std::fs;
+use std::io::Error;
+use std::path::Path;
+use crate::drawing::branch_color;
+use crate::tree::Tree;
+use crate::tree_enum::TreesEnum;
+use crate::tree_index::TreeIndex;
+
+/// The TikZ style names of the branches, later branches are called `Branch7` and so on.
+pub const BRANCH_STYLES: [&str; 6] = ["First", "Second", "Third", "Fourth", "Fifth", "Sixth"];
+/// Width or height of the picture in centimetres, whichever is larger
+pub const DEFAULT_SIZE: f32 = 8.0;
+
+fn style(branch: TreesEnum) -> String {
+    match branch {
+        TreesEnum::Center => "Center".to_string(),
+        TreesEnum::Branch(i) => BRANCH_STYLES.get(i).map(|name| name.to_string()).unwrap_or_else(|| format!("Branch{}", i + 1)),
+    }
+}
+
+fn name(index: TreeIndex) -> String {
+    match index.0 {
+        TreesEnum::Center => "c".to_string(),
+        TreesEnum::Branch(i) => format!("b{}n{}", i, index.1),
+    }
+}
+
+impl Tree {
+    /// A `tikzpicture` with a style per branch and one for the cycle edges,
+    /// the coordinates are scaled so the picture is `size` centimetres wide or high.
+    pub fn to_tikz(&self, cycle: Option<&[TreeIndex]>, size: f32) -> String {
+        let (min, max) = self.bounds();
+        let scale = size / (max - min).max_element().max(f32::EPSILON);
+
+        let mut out = String::new();
+        let _ = writeln!(out, "\\begin{{tikzpicture}}[");
+        let _ = writeln!(out, "    Center/.style={{circle, fill=gray, inner sep=1.5pt}},");
+        for (i, branch) in self.branch_ids().enumerate() {
+            let color = branch_color(i).0;
+            let _ = writeln!(out, "    {}/.style={{circle, fill={}, inner sep=1.5pt}},", style(branch), color);
+        }
+        let _ = writeln!(out, "    steiner/.style={{circle, fill=gray, inner sep=0.75pt}},");
+        let _ = writeln!(out, "    tree/.style={{gray}},");
+        let _ = writeln!(out, "    cycle/.style={{orange, thick}},");
+        let _ = writeln!(out, "]");
+
+        for index in self.iter() {
+            let pos = (self[index].pos - min) * scale;
+            let style = if self[index].steiner { "steiner".to_string() } else { style(index.0) };
+            let _ = writeln!(out, "    \\node[{}] ({}) at ({:.3}, {:.3}) {{}};", style, name(index), pos.x, pos.y);
+        }
+        for index in self.iter().filter(|index| index.0 != TreesEnum::Center) {
+            let parent = match self[index].parent {
+                Some(parent) => TreeIndex(index.0, parent),
+                None => TreeIndex(TreesEnum::Center, 0),
+            };
+            let _ = writeln!(out, "    \\draw[tree] ({}) -- ({});", name(index), name(parent));
+        }
+        if let Some(cycle) = cycle {
+            let path: Vec<String> = cycle.iter().map(|index| format!("({})", name(*index))).collect();
+            let _ = writeln!(out, "    \\draw[cycle] {} -- cycle;", path.join(" -- "));
+        }
+        let _ = writeln!(out, "\\end{{tikzpicture}}");
+        out
+    }
+
+    pub fn save_tikz(&self, path: &Path, cycle: Option<&[TreeIndex]>, size: f32) -> Result<(), Error> {
+        fs::write(path, self.to_tikz(cycle, size))
+    }
+}
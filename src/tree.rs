@@ -1,17 +1,38 @@
+use std::cell::OnceCell;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::ops::{Index, IndexMut};
 use nannou::geom::Point2;
 use crate::node::Node;
 use std::vec::IntoIter;
+use crate::cycle_error::CycleError;
 use crate::edge::Edge;
+use crate::embedding::EmbeddingProblem;
+use crate::greedy::Step;
 use crate::tree_enum::TreesEnum;
+use crate::spatial::EdgeGrid;
 use crate::tree_index::TreeIndex;
+use crate::visibility::VisibilityGraph;
 
 
+#[derive(Clone, Debug)]
 pub struct Tree {
-    pub center: Node,
-    pub tree1: Vec<Node>,
-    pub tree2: Vec<Node>,
-    pub tree3: Vec<Node>,
+    center: Node,
+    /// Every branch is a subtree hanging off the center that holds exactly one corner of the polygon, its last node.
+    /// Nodes can have several children within their branch, but a subtree can't lead to two corners
+    /// unless it goes through the center, and parents are never in another branch.
+    branches: Vec<Vec<Node>>,
+    /// Built on the first visibility query, every change goes through `IndexMut` which calls `invalidate`
+    edge_grid: OnceCell<EdgeGrid>,
+    visibility: OnceCell<VisibilityGraph>,
+    embedding: OnceCell<Vec<EmbeddingProblem>>,
+    generation: u64,
+}
+
+/// Generations are unique over all trees, so a replaced tree never looks unchanged.
+static GENERATIONS: AtomicU64 = AtomicU64::new(0);
+
+fn next_generation() -> u64 {
+    GENERATIONS.fetch_add(1, Ordering::Relaxed)
 }
 
 pub enum Triangle {
@@ -32,6 +53,7 @@ impl Index<TreeIndex> for Tree {
 
 impl IndexMut<TreeIndex> for Tree {
     fn index_mut(&mut self, index: TreeIndex) -> &mut Self::Output {
+        self.invalidate();
         if let TreesEnum::Center = index.0 {
             return &mut self.center;
         }
@@ -44,41 +66,121 @@ impl Index<TreesEnum> for Tree {
 
     fn index(&self, tree: TreesEnum) -> &Self::Output {
         match tree {
-            TreesEnum::First => { &self.tree1 }
-            TreesEnum::Second => { &self.tree2 }
-            TreesEnum::Third => { &self.tree3 }
-            _ => { &self.tree1 }
+            TreesEnum::Branch(i) => { &self.branches[i] }
+            TreesEnum::Center => { panic!("the center is not a branch") }
         }
     }
 }
 
 impl IndexMut<TreesEnum> for Tree {
     fn index_mut(&mut self, tree: TreesEnum) -> &mut Self::Output {
+        self.invalidate();
         match tree {
-            TreesEnum::First => { &mut self.tree1 }
-            TreesEnum::Second => { &mut self.tree2 }
-            TreesEnum::Third => { &mut self.tree3 }
-            _ => { &mut self.tree1 }
+            TreesEnum::Branch(i) => { &mut self.branches[i] }
+            TreesEnum::Center => { panic!("the center is not a branch") }
         }
     }
 }
 
 impl Tree {
-    pub fn empty() -> Self {
+    pub fn empty(branches: usize) -> Self {
         Self {
             center: Node::default(),
-            tree1: vec![],
-            tree2: vec![],
-            tree3: vec![],
+            branches: vec![vec![]; branches],
+            edge_grid: OnceCell::new(),
+            visibility: OnceCell::new(),
+            embedding: OnceCell::new(),
+            generation: next_generation(),
         }
     }
 
+    /// Throws away everything that was computed from the positions of the nodes.
+    fn invalidate(&mut self) {
+        self.edge_grid.take();
+        self.visibility.take();
+        self.embedding.take();
+        self.generation = next_generation();
+    }
+
+    /// Changes whenever the tree might have changed, results computed for the same generation are still valid.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// The tree edges in a grid for quick crossing queries.
+    pub fn edge_grid(&self) -> &EdgeGrid {
+        self.edge_grid.get_or_init(|| EdgeGrid::new(self.get_all_edges()))
+    }
+
+    /// Which nodes can see each other, built on first use and kept up to date by `move_node`.
+    pub fn visibility(&self) -> &VisibilityGraph {
+        self.visibility.get_or_init(|| VisibilityGraph::new(self))
+    }
+
+    /// What `check_embedding` finds, though maybe in another order, built on first use and kept up to date by `move_node`.
+    pub fn embedding_problems(&self) -> &[EmbeddingProblem] {
+        self.embedding.get_or_init(|| self.check_embedding())
+    }
+
+    /// Moves a node and updates the visibility graph and the embedding problems instead of throwing them away.
+    pub fn move_node(&mut self, index: TreeIndex, pos: Point2) {
+        let old_pos = self[index].pos;
+        let graph = self.visibility.take();
+        let problems = self.embedding.take();
+        self[index].pos = pos;
+        if let Some(mut graph) = graph {
+            graph.update_node(self, index, old_pos);
+            let _ = self.visibility.set(graph);
+        }
+        if let Some(mut problems) = problems {
+            self.update_embedding(&mut problems, index, old_pos);
+            let _ = self.embedding.set(problems);
+        }
+    }
+
+    pub fn center(&self) -> &Node {
+        &self.center
+    }
+
+    /// Every branch in order, changing them goes through `IndexMut` so the caches are thrown away.
+    pub fn branches(&self) -> &[Vec<Node>] {
+        &self.branches
+    }
+
+    pub fn branch_ids(&self) -> impl Iterator<Item = TreesEnum> {
+        (0..self.branches.len()).map(TreesEnum::Branch)
+    }
+
+    /// The nodes a cycle has to visit, every node except the Steiner points.
+    pub fn cycle_nodes(&self) -> impl Iterator<Item = TreeIndex> + '_ {
+        self.iter().filter(|index| !self[*index].steiner)
+    }
+
+    /// The length of a cycle that visits every node except the Steiner points.
+    pub fn full_cycle_len(&self) -> usize {
+        self.cycle_nodes().count()
+    }
+
+    /// The shortest and the longest length a cycle can have, the center with one node of every branch
+    /// up to every node except the Steiner points.
+    pub fn cycle_lengths(&self) -> (usize, usize) {
+        (self.branches.len() + 1, self.full_cycle_len())
+    }
+
+    /// Turns a node into a Steiner point or back, the center and the corners always have to be visited.
+    pub fn set_steiner(&mut self, index: TreeIndex, steiner: bool) -> bool {
+        if !self.is_inner_node(index) {
+            return false;
+        }
+        self[index].steiner = steiner;
+        true
+    }
 
     pub fn iter(&self) -> IntoIter<TreeIndex> {
         let mut output = vec![TreeIndex(TreesEnum::Center, 0)];
-        for branch in TreesEnum::iterator() {
-            for i in 0..self[*branch].len() {
-                output.push(TreeIndex(*branch, i))
+        for branch in self.branch_ids() {
+            for i in 0..self[branch].len() {
+                output.push(TreeIndex(branch, i))
             }
         }
         output.into_iter()
@@ -94,161 +196,301 @@ impl Tree {
         None
     }
 
-    pub fn add_node(&mut self, tree: TreesEnum, node: Node) {
+    /// Extends the branch with a node connected to the last node of the branch, or to the center if it is empty.
+    pub fn add_node(&mut self, tree: TreesEnum, mut node: Node) {
+        node.parent = self[tree].len().checked_sub(1);
         self[tree].push(node);
     }
 
-    pub fn insert_node(&mut self, index: usize, tree: TreesEnum, node: Node) {
-        self[tree].insert(index, node);
+    /// Splits the edge between the node at `index` and its parent with `node`,
+    /// or extends the branch when `index` is the length of the branch.
+    pub fn insert_node(&mut self, index: usize, tree: TreesEnum, mut node: Node) {
+        if index == self[tree].len() {
+            self.add_node(tree, node);
+            return;
+        }
+        node.parent = self[tree][index].parent.map(|p| if p >= index { p + 1 } else { p });
+        self.restore_node(TreeIndex(tree, index), node, &[index + 1]);
+    }
+
+    /// Adds a leaf connected to `parent`, just before the corner of the branch.
+    pub fn add_child(&mut self, parent: TreeIndex, mut node: Node) -> Option<TreeIndex> {
+        if parent.0 == TreesEnum::Center {
+            return None;
+        }
+        let index = self[parent.0].len() - 1;
+        node.parent = Some(if parent.1 >= index { parent.1 + 1 } else { parent.1 });
+        self.restore_node(TreeIndex(parent.0, index), node, &[]);
+        Some(TreeIndex(parent.0, index))
+    }
+
+    /// The indices of all nodes in the same branch that have `index` as their parent.
+    pub fn children(&self, index: TreeIndex) -> Vec<usize> {
+        if index.0 == TreesEnum::Center {
+            return vec![];
+        }
+        (0..self[index.0].len()).filter(|i| self[index.0][*i].parent == Some(index.1)).collect()
+    }
+
+    /// Removes a node and connects its children to its parent.
+    /// Returns the node and its children, with indices from before the removal, so `restore_node` can undo it.
+    pub fn take_node(&mut self, index: TreeIndex) -> (Node, Vec<usize>) {
+        let children = self.children(index);
+        let branch = &mut self[index.0];
+        let node = branch.remove(index.1);
+        for other in branch.iter_mut() {
+            if other.parent == Some(index.1) {
+                other.parent = node.parent;
+            }
+            other.parent = other.parent.map(|p| if p > index.1 { p - 1 } else { p });
+        }
+        (node, children)
+    }
+
+    /// Puts a node back at `index`, making it the parent of `children`.
+    /// The parent of the node and the children use the indices from after the insertion.
+    pub fn restore_node(&mut self, index: TreeIndex, node: Node, children: &[usize]) {
+        let branch = &mut self[index.0];
+        for other in branch.iter_mut() {
+            other.parent = other.parent.map(|p| if p >= index.1 { p + 1 } else { p });
+        }
+        branch.insert(index.1, node);
+        for child in children {
+            branch[*child].parent = Some(index.1);
+        }
+    }
+
+    /// Removes a node from its branch, the center and the corners of the polygon can't be removed.
+    pub fn remove_node(&mut self, index: TreeIndex) -> Option<(Node, Vec<usize>)> {
+        if !self.is_inner_node(index) {
+            return None;
+        }
+        Some(self.take_node(index))
+    }
+
+    /// Moves a node along the tree path to the node at `to`, the nodes in between shift one step back.
+    /// Nodes keep their position and the tree edges change, the center and the corners can't be part of the path.
+    pub fn reorder_node(&mut self, index: TreeIndex, to: usize) -> bool {
+        if !self.is_inner_node(index) || !self.is_inner_node(TreeIndex(index.0, to)) {
+            return false;
+        }
+        let Some(path) = self.path_between(index.0, index.1, to) else {
+            return false;
+        };
+        if path.iter().any(|i| !self.is_inner_node(TreeIndex(index.0, *i))) {
+            return false;
+        }
+        // The parents describe the shape of the tree and stay where they are, everything else moves with the node
+        let branch = &mut self[index.0];
+        let parents: Vec<Option<usize>> = path.iter().map(|i| branch[*i].parent).collect();
+        let moved = branch[path[0]];
+        for k in 0..path.len() - 1 {
+            branch[path[k]] = branch[path[k + 1]];
+        }
+        branch[path[path.len() - 1]] = moved;
+        for (i, parent) in path.iter().zip(parents) {
+            branch[*i].parent = parent;
+        }
+        true
+    }
+
+    /// The next node on the tree path from a node to the corner of its branch.
+    pub fn next_towards_corner(&self, index: TreeIndex) -> Option<usize> {
+        if index.0 == TreesEnum::Center {
+            return None;
+        }
+        let path = self.path_between(index.0, index.1, self[index.0].len() - 1)?;
+        path.get(1).copied()
+    }
+
+    /// The nodes on the tree path from `a` to `b` within a branch, `None` if the path goes through the center.
+    fn path_between(&self, branch: TreesEnum, a: usize, b: usize) -> Option<Vec<usize>> {
+        let (up_a, up_b) = (self.ancestors(branch, a)?, self.ancestors(branch, b)?);
+        let common = up_a.iter().position(|i| up_b.contains(i))?;
+        let mut output = up_a[..=common].to_vec();
+        let common_in_b = up_b.iter().position(|i| *i == up_a[common]).unwrap();
+        output.extend(up_b[..common_in_b].iter().rev());
+        Some(output)
+    }
+
+    /// The node followed by its parent, its parent's parent and so on up to the node connected to the center.
+    /// `None` if the parents don't lead to the center.
+    fn ancestors(&self, branch: TreesEnum, mut i: usize) -> Option<Vec<usize>> {
+        let nodes = &self[branch];
+        let mut output = vec![i];
+        while let Some(parent) = nodes.get(i)?.parent {
+            if output.len() > nodes.len() {
+                return None;
+            }
+            output.push(parent);
+            i = parent;
+        }
+        Some(output)
+    }
+
+    /// The node on the tree path from the corner of the branch to the center that is closest to the center
+    /// and isn't a Steiner point, `None` if the branch is empty or its corner isn't connected to the center.
+    pub fn branch_start(&self, branch: TreesEnum) -> Option<TreeIndex> {
+        let corner = self[branch].len().checked_sub(1)?;
+        self.ancestors(branch, corner)?
+            .into_iter()
+            .rev()
+            .find(|i| !self[branch][*i].steiner)
+            .map(|i| TreeIndex(branch, i))
+    }
+
+    fn is_inner_node(&self, index: TreeIndex) -> bool {
+        index.0 != TreesEnum::Center && index.1 + 1 < self[index.0].len()
     }
 
     pub fn get_all_edges(&self) -> Vec<Edge> {
         let mut output = vec![];
-        for tree_branch in TreesEnum::iterator() {
-            output.append(&mut self.get_tree_edges(*tree_branch))
+        for tree_branch in self.branch_ids() {
+            output.append(&mut self.get_tree_edges(tree_branch))
         }
         output
     }
 
     pub fn get_tree_edges(&self, branch: TreesEnum) -> Vec<Edge> {
         let mut output = vec![];
-        for i in 0..self[branch].len() - 1 {
-            // Print the current element and the next element
-            output.push(Edge(self[branch][i].pos, self[branch][i + 1].pos));
+        for node in &self[branch] {
+            let parent = match node.parent {
+                Some(parent) => self[branch][parent].pos,
+                None => self.center.pos,
+            };
+            output.push(Edge(node.pos, parent));
         }
-        output.push(Edge(self[branch].first().unwrap().pos, self.center.pos));
         output
     }
 
+    /// The node whose edge to its parent is closest to `pos`, splitting that edge with `insert_node` puts a node on it.
+    /// `None` if no edge is within `max_distance`.
+    pub fn nearest_tree_edge(&self, pos: Point2, max_distance: f32) -> Option<TreeIndex> {
+        self.iter()
+            .filter(|index| index.0 != TreesEnum::Center)
+            .map(|index| {
+                let parent = match self[index].parent {
+                    Some(parent) => self[index.0][parent].pos,
+                    None => self.center.pos,
+                };
+                (index, Edge(self[index].pos, parent).distance_to(pos))
+            })
+            .filter(|(_, distance)| *distance <= max_distance)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(index, _)| index)
+    }
+
+    /// Checks if any two edges of the tree cross each other.
+    pub fn tree_edges_cross(&self) -> bool {
+        let grid = self.edge_grid();
+        grid.edges().iter().any(|a| grid.any(*a, |b| a.crosses(*b)))
+    }
+
     pub fn find_special_nodes(&self) -> Vec<TreeIndex> {
         let mut output = vec![];
         for node in self.iter() {
-            if self.check_node_vis(node).len() == 3 {
+            if self.check_node_vis(node).len() == self.branches.len() {
                 output.push(node);
             }
         }
         output
     }
 
-    pub fn find_cycle(&self, length: usize) -> Result<Vec<TreeIndex>, ()> {
-        //Get the basic start cycle
-        let mut cycle = self.center_triangle();
+    pub fn find_cycle(&self, length: usize) -> Result<Vec<TreeIndex>, CycleError> {
+        self.find_cycle_cancellable(length, &AtomicBool::new(false), &mut |_| {})
+    }
 
-        //Strict pass
-        let mut strict = true;
-        'strict: while cycle.len() != length {
-            let prev_length = cycle.len();
-            'cycle: for (prev, next) in (0..prev_length).zip((0..prev_length).cycle().skip(1)) {
-                // if this is the strict pass the edges must be of the same edge
-                if !cycle[prev].same_color(cycle[next]) && strict {
-                    continue 'cycle;
-                }
-                // Get a list of all visible nodes from the first node
-                let visible = self.check_node_vis_cycle_org(cycle[prev], &cycle);
-                // For all of them
-                'inner: for visible_node in visible {
-                    match visible_node.makes_triangle((cycle[next], cycle[prev])) {
-                        Triangle::AllSame => {}
-                        Triangle::OneOdd => {
-                            if strict {
-                                continue 'inner;
-                            }
-                        }
-                        Triangle::Illegal => {
-                            continue 'inner;
-                        }
-                    }
-                    let other_vis = self.check_node_vis_cycle_org(cycle[next], &cycle);
-                    if cycle.contains(&visible_node) {
-                        continue 'inner;
-                    }
-                    if other_vis.contains(&visible_node) {
-                        cycle.insert(next, visible_node);
-                        break 'cycle;
-                    }
-                }
+    /// `find_cycle` that stops with `CycleError::Cancelled` once `cancel` is set
+    /// and calls `progress` with the length of the cycle after every inserted node.
+    pub fn find_cycle_cancellable(&self, length: usize, cancel: &AtomicBool, progress: &mut dyn FnMut(usize)) -> Result<Vec<TreeIndex>, CycleError> {
+        let mut steps = self.greedy_steps(length);
+        while let Some(step) = steps.next() {
+            if cancel.load(Ordering::Relaxed) {
+                return Err(CycleError::Cancelled);
             }
-            if cycle.len() == prev_length {
-                if strict {
-                    strict = false;
-                    continue 'strict;
-                } else {
-                    return Err(());
-                }
+            match step {
+                Step::Start(_) | Step::Insert { .. } => progress(steps.current_cycle().len()),
+                Step::Finished(result) => return result,
+                Step::Candidate { .. } | Step::Relaxed => {}
             }
         }
-        Ok(cycle)
+        // The steps end with `Finished`, this only happens if that ever changes
+        let cycle = steps.current_cycle();
+        let unreachable = self.cycle_nodes().filter(|index| !cycle.contains(index)).collect();
+        Err(CycleError::Stalled { length: cycle.len(), unreachable })
     }
 
+    /// The center followed by the corner of every branch, empty branches have no corner and are skipped.
     pub fn start_cycle(&self) -> Vec<TreeIndex> {
-        let c = TreeIndex(TreesEnum::Center, 0);
-        let p1 = TreeIndex(TreesEnum::First, self.tree1.len()-1);
-        let p2 = TreeIndex(TreesEnum::Second, self.tree2.len()-1);
-        let p3 = TreeIndex(TreesEnum::Third, self.tree3.len()-1);
-        vec![c, p1, p2, p3]
+        let mut output = vec![TreeIndex(TreesEnum::Center, 0)];
+        for branch in self.branch_ids() {
+            if let Some(corner) = self[branch].len().checked_sub(1) {
+                output.push(TreeIndex(branch, corner));
+            }
+        }
+        output
     }
 
     pub fn find_smallest_triangle(&self) -> Vec<TreeIndex> {
         let mut all: Vec<TreeIndex> = self.check_node_vis(TreeIndex(TreesEnum::Center, 0));
+        all.retain(|index| !self[*index].steiner);
         all.sort_by(|a, b | self[*a].dist(&self.center).partial_cmp(&self[*b].dist(&self.center)).unwrap());
         let mut output = vec![TreeIndex(TreesEnum::Center, 0)];
-        let (mut f, mut s,mut t) = (false, false, false);
+        let mut found = vec![false; self.branches.len()];
         for node in all {
-            match node.0 {
-                TreesEnum::Center => {}
-                TreesEnum::First => {
-                    if !f {
-                        f = true;
-                        output.push(node);
-                    }
+            if let TreesEnum::Branch(i) = node.0 {
+                if !found[i] {
+                    found[i] = true;
+                    output.push(node);
                 }
-                TreesEnum::Second => {
-                    if !s {
-                        s = true;
-                        output.push(node);
-                    }}
-                TreesEnum::Third => {
-                    if !t {
-                        t = true;
-                        output.push(node);
-                    }}
             }
-            if f && s && t {
+            if found.iter().all(|f| *f) {
                 break;
             }
         }
-        if !f || !s || !t {
+        if !found.iter().all(|f| *f) {
             output = vec![]
-        } else if self.verify_valid_4th(&output) {
+        } else if self.is_simple_cycle(&output) {
             output.swap(0, 1);
         }
         output
     }
 
-    pub fn verify_valid_4th(&self, cycle: &[TreeIndex]) -> bool {
-        let edges: [Edge; 4] = [
-            Edge(self[cycle[0]].pos, self[cycle[1]].pos),
-            Edge(self[cycle[1]].pos, self[cycle[2]].pos),
-            Edge(self[cycle[2]].pos, self[cycle[3]].pos),
-            Edge(self[cycle[3]].pos, self[cycle[0]].pos),
-        ];
-        !(edges[0].intersects(edges[2]) || edges[1].intersects(edges[3]))
-    }
-
-    pub fn center_triangle(&self) -> Vec<TreeIndex> {
-        let mut output = vec![
-            TreeIndex(TreesEnum::Center, 0),
-            TreeIndex(TreesEnum::First, 0),
-            TreeIndex(TreesEnum::Second, 0),
-            TreeIndex(TreesEnum::Third, 0)
-        ];
-        if !self.verify_valid_4th(&output) {
-            output.swap(0, 1);
-            if !self.verify_valid_4th(&output) {
-                output.swap(1, 2);
+    /// Checks that no two edges of the cycle cross, except for neighbouring edges meeting in their shared node.
+    pub fn is_simple_cycle(&self, cycle: &[TreeIndex]) -> bool {
+        let edges = self.get_cycle_edges(cycle);
+        for i in 0..edges.len() {
+            for j in i + 2..edges.len() {
+                if (i == 0 && j == edges.len() - 1) || !edges[i].intersects(edges[j]) {
+                    continue;
+                }
+                return false;
             }
         }
-        output
+        true
+    }
+
+    /// The center followed by the `branch_start` of every branch,
+    /// with the center moved along the cycle until it crosses neither itself nor the tree, or at least not itself.
+    pub fn center_triangle(&self) -> Result<Vec<TreeIndex>, CycleError> {
+        let firsts = self.branch_ids()
+            .map(|branch| self.branch_start(branch).ok_or(CycleError::BrokenBranch(branch)))
+            .collect::<Result<Vec<TreeIndex>, CycleError>>()?;
+        let with_center_at = |position: usize| {
+            let mut output = firsts.clone();
+            output.insert(position, TreeIndex(TreesEnum::Center, 0));
+            output
+        };
+        Ok((0..firsts.len())
+            .map(with_center_at)
+            .find(|cycle| self.is_simple_cycle(cycle) && !self.cycle_crosses_tree(cycle))
+            .or_else(|| (0..firsts.len()).map(with_center_at).find(|cycle| self.is_simple_cycle(cycle)))
+            .unwrap_or_else(|| with_center_at(firsts.len() - 1)))
+    }
+
+    /// Checks if any edge of the cycle crosses a tree edge, reusing a tree edge is fine.
+    pub fn cycle_crosses_tree(&self, cycle: &[TreeIndex]) -> bool {
+        self.get_cycle_edges(cycle).into_iter().any(|edge| self.crosses_tree(edge))
     }
 
     pub fn get_cycle_edges(&self, cycle: &[TreeIndex]) -> Vec<Edge> {
@@ -260,9 +502,15 @@ impl Tree {
     }
 
     pub fn check_node_vis_cycle_org(&self, node_index: TreeIndex, cycle: &[TreeIndex]) -> Vec<TreeIndex> {
-        let mut edges = self.get_cycle_edges(cycle);
-        edges.append(&mut self.get_all_edges());
-        self.check_node_vis_from_edge(node_index, edges)
+        let cycle_edges = self.get_cycle_edges(cycle);
+        self.check_node_vis(node_index)
+            .into_iter()
+            .filter(|index| !self[*index].steiner)
+            .filter(|index| {
+                let new_edge = Edge(self[node_index].pos, self[*index].pos);
+                !cycle_edges.iter().any(|edge| new_edge.crosses(*edge))
+            })
+            .collect()
     }
 
     pub fn check_node_vis_cycle(&self, node_index: TreeIndex, cycle: &[TreeIndex]) -> Vec<TreeIndex> {
@@ -277,14 +525,8 @@ impl Tree {
                 continue 'node;
             }
             let new_edge = Edge(self[node_index].pos, self[index].pos);
-            'edge: for edge in &edges {
-                if edge.0 == self[node_index].pos || edge.1 == self[node_index].pos {
-                    continue 'edge;
-                }
-                if edge.0 == self[index].pos || edge.1 == self[index].pos {
-                    continue 'edge;
-                }
-                if new_edge.intersects(*edge) {
+            for edge in &edges {
+                if new_edge.crosses(*edge) {
                     continue 'node;
                 }
             }
@@ -293,8 +535,232 @@ impl Tree {
         output
     }
 
+    /// Checks if an edge crosses any of the tree edges, using the edge grid.
+    pub fn crosses_tree(&self, edge: Edge) -> bool {
+        self.edge_grid().any(edge, |tree_edge| edge.crosses(*tree_edge))
+    }
+
     pub fn check_node_vis(&self, node_index: TreeIndex) -> Vec<TreeIndex> {
-        let all_edges = self.get_all_edges();
-        self.check_node_vis_from_edge(node_index, all_edges)
+        self.visibility().visible_from(node_index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+    use crate::random::random_trees;
+    use crate::validate::Violation;
+
+    fn small_tree() -> Tree {
+        let mut tree = Tree::empty(3);
+        tree.add_node(TreesEnum::Branch(0), Node::from_pos(0.0, -50.0));
+        tree.add_node(TreesEnum::Branch(0), Node::from_pos(0.0, -100.0));
+        tree.add_node(TreesEnum::Branch(1), Node::from_pos(-100.0, 100.0));
+        tree.add_node(TreesEnum::Branch(2), Node::from_pos(50.0, 50.0));
+        tree.add_node(TreesEnum::Branch(2), Node::from_pos(100.0, 100.0));
+        tree
+    }
+
+    #[test]
+    fn iter_starts_at_the_center_and_walks_the_branches_in_order() {
+        let nodes: Vec<TreeIndex> = small_tree().iter().collect();
+        assert_eq!(nodes, vec![
+            TreeIndex(TreesEnum::Center, 0),
+            TreeIndex(TreesEnum::Branch(0), 0),
+            TreeIndex(TreesEnum::Branch(0), 1),
+            TreeIndex(TreesEnum::Branch(1), 0),
+            TreeIndex(TreesEnum::Branch(2), 0),
+            TreeIndex(TreesEnum::Branch(2), 1),
+        ]);
+    }
+
+    #[test]
+    fn index_reads_and_writes_the_same_node() {
+        let mut tree = small_tree();
+        let index = TreeIndex(TreesEnum::Branch(2), 1);
+        assert_eq!(tree[index].pos, Point2::new(100.0, 100.0));
+        assert_eq!(tree[index].parent, Some(0));
+        tree[index].pos = Point2::new(120.0, 90.0);
+        assert_eq!(tree[index].pos, Point2::new(120.0, 90.0));
+        assert_eq!(tree[TreesEnum::Branch(2)][1].pos, Point2::new(120.0, 90.0));
+        assert_eq!(tree.branches[2][1].pos, Point2::new(120.0, 90.0));
+
+        let center = TreeIndex(TreesEnum::Center, 0);
+        tree[center].pos = Point2::new(1.0, 2.0);
+        assert_eq!(tree.center.pos, Point2::new(1.0, 2.0));
+    }
+
+    #[test]
+    fn index_mut_changes_the_generation() {
+        let mut tree = small_tree();
+        let generation = tree.generation();
+        let _ = &tree[TreeIndex(TreesEnum::Branch(0), 0)];
+        assert_eq!(tree.generation(), generation);
+        tree[TreeIndex(TreesEnum::Branch(0), 0)].pos.x += 1.0;
+        assert_ne!(tree.generation(), generation);
+    }
+
+    #[test]
+    #[should_panic]
+    fn the_center_is_not_a_branch() {
+        let _ = &small_tree()[TreesEnum::Center];
+    }
+
+    #[test]
+    fn insert_node_splits_the_edge_above() {
+        let mut tree = small_tree();
+        tree.insert_node(1, TreesEnum::Branch(0), Node::from_pos(5.0, -75.0));
+        assert_eq!(tree[TreesEnum::Branch(0)].len(), 3);
+        assert_eq!(tree[TreeIndex(TreesEnum::Branch(0), 1)].parent, Some(0));
+        assert_eq!(tree[TreeIndex(TreesEnum::Branch(0), 2)].parent, Some(1));
+    }
+
+    #[test]
+    fn find_cycle_on_a_small_tree() {
+        let tree = small_tree();
+        let cycle = tree.find_cycle(6).unwrap();
+        assert_eq!(cycle.len(), 6);
+        assert!(tree.validate_hamiltonian_cycle(&cycle).is_empty());
+    }
+
+    #[test]
+    fn steiner_points_are_left_out_of_cycles() {
+        let mut tree = small_tree();
+        let steiner = TreeIndex(TreesEnum::Branch(0), 0);
+        assert!(tree.set_steiner(steiner, true));
+        assert_eq!(tree.full_cycle_len(), 5);
+        assert_eq!(tree.branch_start(TreesEnum::Branch(0)), Some(TreeIndex(TreesEnum::Branch(0), 1)));
+        let cycle = tree.find_cycle(5).unwrap();
+        assert!(!cycle.contains(&steiner));
+        assert!(tree.validate_hamiltonian_cycle(&cycle).is_empty());
+        assert!(tree.validate_cycle(&[TreeIndex(TreesEnum::Center, 0), steiner, TreeIndex(TreesEnum::Branch(1), 0)])
+            .contains(&Violation::SteinerNode(steiner)));
+    }
+
+    #[test]
+    fn the_center_and_the_corners_cant_be_steiner_points() {
+        let mut tree = small_tree();
+        assert!(!tree.set_steiner(TreeIndex(TreesEnum::Center, 0), true));
+        assert!(!tree.set_steiner(TreeIndex(TreesEnum::Branch(0), 1), true));
+        assert_eq!(tree.full_cycle_len(), 6);
+    }
+
+    #[test]
+    fn lengths_outside_the_range_are_rejected() {
+        let tree = small_tree();
+        assert_eq!(tree.cycle_lengths(), (4, 6));
+        for length in [2, 3, 7] {
+            assert_eq!(tree.find_cycle(length), Err(CycleError::InvalidLength { length, shortest: 4, longest: 6 }));
+        }
+    }
+
+    #[test]
+    fn a_branch_without_nodes_has_no_start() {
+        let mut tree = Tree::empty(3);
+        tree.add_node(TreesEnum::Branch(0), Node::from_pos(0.0, -100.0));
+        tree.add_node(TreesEnum::Branch(1), Node::from_pos(-100.0, 100.0));
+        assert_eq!(tree.center_triangle(), Err(CycleError::BrokenBranch(TreesEnum::Branch(2))));
+        assert_eq!(tree.find_cycle(3), Err(CycleError::BrokenBranch(TreesEnum::Branch(2))));
+        assert_eq!(tree.start_cycle().len(), 3);
+        // Everything that draws or checks the tree works without the missing corner
+        tree.check_embedding();
+        tree.move_adds_problems(TreeIndex(TreesEnum::Branch(0), 0), Point2::new(10.0, -90.0));
+        tree.to_svg(None);
+        tree.to_canvas(None, 100);
+    }
+
+    #[test]
+    fn reorder_moves_the_whole_node() {
+        let mut tree = small_tree();
+        tree.insert_node(1, TreesEnum::Branch(0), Node::from_pos(5.0, -75.0));
+        let (first, second) = (TreeIndex(TreesEnum::Branch(0), 0), TreeIndex(TreesEnum::Branch(0), 1));
+        tree.set_steiner(first, true);
+        assert!(tree.reorder_node(first, 1));
+        assert_eq!((tree[first].pos, tree[first].steiner, tree[first].parent), (Point2::new(5.0, -75.0), false, None));
+        assert_eq!((tree[second].pos, tree[second].steiner, tree[second].parent), (Point2::new(0.0, -50.0), true, Some(0)));
+    }
+
+    #[test]
+    fn finds_cycles_on_known_solvable_trees() {
+        // The greedy search fails on some seeds, the first with these counts is 536
+        for seed in 0..20 {
+            let tree = Tree::random(&[5, 5, 5], seed);
+            let cycle = tree.find_cycle(tree.full_cycle_len()).unwrap_or_else(|err| panic!("seed {}: {}", seed, err));
+            assert!(tree.validate_hamiltonian_cycle(&cycle).is_empty(), "seed {}", seed);
+        }
+    }
+
+    #[test]
+    fn the_first_failing_seed_stalls() {
+        let tree = Tree::random(&[5, 5, 5], 536);
+        let unreachable = (2..5).map(|i| TreeIndex(TreesEnum::Branch(2), i)).collect();
+        assert_eq!(tree.find_cycle(tree.full_cycle_len()), Err(CycleError::Stalled { length: 13, unreachable }));
+    }
+
+    proptest! {
+        #[test]
+        fn iter_visits_every_node_once_in_order(tree in random_trees(1..5, 3..6)) {
+            let nodes: Vec<TreeIndex> = tree.iter().collect();
+            prop_assert_eq!(nodes.len(), 1 + tree.branches.iter().map(Vec::len).sum::<usize>());
+            prop_assert_eq!(nodes[0], TreeIndex(TreesEnum::Center, 0));
+            for pair in nodes[1..].windows(2) {
+                let (TreesEnum::Branch(a), TreesEnum::Branch(b)) = (pair[0].0, pair[1].0) else {
+                    return Err(TestCaseError::fail("the center came up twice"));
+                };
+                prop_assert!(a < b || (a == b && pair[0].1 + 1 == pair[1].1));
+            }
+        }
+
+        #[test]
+        fn index_reads_what_index_mut_wrote(mut tree in random_trees(1..5, 3..6)) {
+            // Every node gets its own position, so writing to the wrong node shows up when reading another one
+            let nodes: Vec<TreeIndex> = tree.iter().collect();
+            for (i, index) in nodes.iter().enumerate() {
+                tree[*index].pos = Point2::new(i as f32, -(i as f32));
+                tree[*index].steiner = i % 2 == 0;
+            }
+            for (i, index) in nodes.iter().enumerate() {
+                prop_assert_eq!(tree[*index].pos, Point2::new(i as f32, -(i as f32)));
+                prop_assert_eq!(tree[*index].steiner, i % 2 == 0);
+            }
+        }
+
+        #[test]
+        fn center_triangle_doesnt_cross_itself(tree in random_trees(1..5, 3..6)) {
+            let start = tree.center_triangle().unwrap();
+            prop_assert_eq!(start.len(), tree.branches.len() + 1);
+            prop_assert!(tree.is_simple_cycle(&start));
+        }
+
+        #[test]
+        fn found_cycles_are_simple_and_have_the_right_length(tree in random_trees(1..5, 3..6), fraction in 0.0..=1.0f64) {
+            let (shortest, longest) = tree.cycle_lengths();
+            let length = shortest + ((longest - shortest) as f64 * fraction).round() as usize;
+            match tree.find_cycle(length) {
+                Ok(cycle) => {
+                    prop_assert_eq!(cycle.len(), length);
+                    prop_assert!(tree.is_simple_cycle(&cycle));
+                    prop_assert_eq!(tree.validate_greedy_cycle(&cycle), vec![]);
+                }
+                Err(CycleError::SelfCrossingStart { start }) => prop_assert!(!tree.is_simple_cycle(&start)),
+                Err(CycleError::StartCrossesTree { start, length: reached }) => {
+                    prop_assert_eq!(reached, length);
+                    prop_assert!(tree.cycle_crosses_tree(&start));
+                }
+                Err(CycleError::Stalled { length: reached, unreachable }) => {
+                    prop_assert!(shortest <= reached && reached < length);
+                    prop_assert_eq!(unreachable.len(), longest - reached);
+                }
+                Err(err) => prop_assert!(false, "the greedy search can't fail with {:?}", err),
+            }
+        }
+
+        #[test]
+        fn a_good_start_is_a_cycle_of_its_own_length(tree in random_trees(1..5, 3..6)) {
+            let start = tree.center_triangle().unwrap();
+            prop_assume!(tree.is_simple_cycle(&start) && !tree.cycle_crosses_tree(&start));
+            prop_assert_eq!(tree.find_cycle(start.len()), Ok(start));
+        }
     }
 }
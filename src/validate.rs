@@ -0,0 +1,105 @@
+use crate::edge::Edge;
+use crate::tree::Tree;
+use crate::tree_enum::TreesEnum;
+use crate::tree_index::TreeIndex;
+
+/// A single rule that a cycle breaks, cycle edges are referred to by the index of their first node.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Violation {
+    /// A cycle needs at least three nodes
+    TooShort(usize),
+    DuplicateNode(TreeIndex),
+    /// Only reported when the cycle has to be hamiltonian
+    MissingNode(TreeIndex),
+    /// Steiner points are part of the tree, but never of a cycle
+    SteinerNode(TreeIndex),
+    TreeCrossing {
+        cycle_edge: usize,
+        tree_edge: Edge,
+    },
+    CycleCrossing(usize, usize),
+    /// The cycle enters a branch it already left before, so the branch isn't visited in one run.
+    /// This isn't one of the rules of the problem: the greedy search only inserts a node next to one of
+    /// its own branch (see `TreeIndex::makes_triangle`), so its cycles never do this, the exact solver's can.
+    ColourJump(TreeIndex, TreeIndex),
+}
+
+impl Tree {
+    /// Independently checks the rules of a cycle from the README and returns every violation it finds.
+    pub fn validate_cycle(&self, cycle: &[TreeIndex]) -> Vec<Violation> {
+        let mut output = vec![];
+        if cycle.len() < 3 {
+            output.push(Violation::TooShort(cycle.len()));
+            return output;
+        }
+        for (i, index) in cycle.iter().enumerate() {
+            if cycle[..i].contains(index) && !output.contains(&Violation::DuplicateNode(*index)) {
+                output.push(Violation::DuplicateNode(*index));
+            }
+            if self[*index].steiner && !output.contains(&Violation::SteinerNode(*index)) {
+                output.push(Violation::SteinerNode(*index));
+            }
+        }
+
+        let cycle_edges = self.get_cycle_edges(cycle);
+        let tree_edges = self.get_all_edges();
+        for (i, cycle_edge) in cycle_edges.iter().enumerate() {
+            for tree_edge in &tree_edges {
+                if cycle_edge.crosses(*tree_edge) {
+                    output.push(Violation::TreeCrossing { cycle_edge: i, tree_edge: *tree_edge });
+                }
+            }
+            for (j, other) in cycle_edges.iter().enumerate().skip(i + 1) {
+                if cycle_edge.crosses(*other) {
+                    output.push(Violation::CycleCrossing(i, j));
+                }
+            }
+        }
+
+        output
+    }
+
+    /// Same as `validate_cycle`, but also checks that the cycle keeps the branches in one run each like the greedy search does.
+    pub fn validate_greedy_cycle(&self, cycle: &[TreeIndex]) -> Vec<Violation> {
+        let mut output = self.validate_cycle(cycle);
+        if cycle.len() >= 3 {
+            output.append(&mut self.find_colour_jumps(cycle));
+        }
+        output
+    }
+
+    /// Same as `validate_cycle`, but also requires every node of the tree except the Steiner points to be in the cycle.
+    pub fn validate_hamiltonian_cycle(&self, cycle: &[TreeIndex]) -> Vec<Violation> {
+        let mut output = self.validate_cycle(cycle);
+        for index in self.cycle_nodes() {
+            if !cycle.contains(&index) {
+                output.push(Violation::MissingNode(index));
+            }
+        }
+        output
+    }
+
+    fn find_colour_jumps(&self, cycle: &[TreeIndex]) -> Vec<Violation> {
+        // The center can be next to any branch, so it is left out when looking at the colours
+        let branches: Vec<TreeIndex> = cycle.iter().filter(|index| index.0 != TreesEnum::Center).copied().collect();
+        let len = branches.len();
+        // Start at the beginning of a run, so the first run isn't split in two
+        let Some(start) = (0..len).find(|i| branches[*i].0 != branches[(i + len - 1) % len].0) else {
+            return vec![];
+        };
+        let mut output = vec![];
+        let mut seen = vec![];
+        for i in 0..len {
+            let prev = branches[(start + i + len - 1) % len];
+            let next = branches[(start + i) % len];
+            if prev.0 == next.0 {
+                continue;
+            }
+            if seen.contains(&next.0) {
+                output.push(Violation::ColourJump(prev, next));
+            }
+            seen.push(next.0);
+        }
+        output
+    }
+}
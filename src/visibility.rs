@@ -0,0 +1,516 @@
+use std::cmp::Ordering;
+use nannou::geom::Point2;
+use crate::edge::Edge;
+use crate::embedding::in_polygon;
+use crate::predicates::orient2d;
+use crate::spatial::PointGrid;
+use crate::tree::Tree;
+use crate::tree_enum::TreesEnum;
+use crate::tree_index::TreeIndex;
+
+/// For every pair of nodes, whether the segment between them crosses none of the tree edges.
+/// Nodes are numbered in the order of `Tree::iter`.
+#[derive(Clone, Debug)]
+pub struct VisibilityGraph {
+    nodes: Vec<TreeIndex>,
+    /// Where the numbers of every branch start, the center is number 0
+    offsets: Vec<usize>,
+    visible: Vec<Vec<bool>>,
+    /// Whether no two tree edges cross
+    ordered: bool,
+}
+
+/// The positions and tree edges of a tree, with nodes numbered like the graph.
+struct Layout {
+    points: Vec<Point2>,
+    edges: Vec<(usize, usize)>,
+    incident: Vec<Vec<usize>>,
+    /// Whether no two edges cross, only then the edges along a ray have an order that doesn't change
+    ordered: bool,
+}
+
+impl VisibilityGraph {
+    /// Builds the graph with a rotational sweep around every node, as in Lee's algorithm.
+    /// A sweep sorts the nodes by angle and keeps the edges crossed by the ray ordered by distance,
+    /// so every node is only checked against the nearest one and a tree with n nodes takes O(n² log n).
+    /// If tree edges cross there is no such order and every node is checked against all crossed edges, O(n³) at worst.
+    pub fn new(tree: &Tree) -> Self {
+        let nodes: Vec<TreeIndex> = tree.iter().collect();
+        let mut offsets = vec![];
+        let mut offset = 1;
+        for branch in tree.branches() {
+            offsets.push(offset);
+            offset += branch.len();
+        }
+        let mut graph = Self {
+            visible: vec![vec![false; nodes.len()]; nodes.len()],
+            nodes,
+            offsets,
+            ordered: !tree.tree_edges_cross(),
+        };
+        let layout = graph.layout(tree);
+        for v in 0..graph.nodes.len() {
+            graph.sweep(&layout, v);
+        }
+        graph
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+
+    pub fn number(&self, index: TreeIndex) -> usize {
+        match index.0 {
+            TreesEnum::Center => 0,
+            TreesEnum::Branch(i) => self.offsets[i] + index.1,
+        }
+    }
+
+    pub fn is_visible(&self, a: TreeIndex, b: TreeIndex) -> bool {
+        self.visible[self.number(a)][self.number(b)]
+    }
+
+    /// All nodes that can be seen from `index`, in the order of `Tree::iter`.
+    pub fn visible_from(&self, index: TreeIndex) -> Vec<TreeIndex> {
+        let row = &self.visible[self.number(index)];
+        self.nodes.iter().zip(row).filter(|(_, visible)| **visible).map(|(node, _)| *node).collect()
+    }
+
+    /// Updates the graph after the node at `index` moved away from `old_pos`.
+    /// A pair can only change if its segment crosses the old or the new edge to some neighbour, but not both.
+    /// Then it leaves the triangle between the two edges through the path of the moved node or ends inside it,
+    /// so only those pairs are found with a grid over the nodes and checked again. The moved node gets a new sweep.
+    pub fn update_node(&mut self, tree: &Tree, index: TreeIndex, old_pos: Point2) {
+        let mut layout = self.layout(tree);
+        let x = self.number(index);
+        let new_pos = layout.points[x];
+        let neighbours: Vec<Point2> = layout.incident[x].iter()
+            .map(|e| layout.points[if layout.edges[*e].0 == x { layout.edges[*e].1 } else { layout.edges[*e].0 }])
+            .collect();
+        let old_edges: Vec<Edge> = neighbours.iter().map(|n| Edge(old_pos, *n)).collect();
+        let new_edges: Vec<Edge> = neighbours.iter().map(|n| Edge(new_pos, *n)).collect();
+        // Only the edges of the moved node can start crossing, but any crossing can have gone away
+        self.ordered = if self.ordered {
+            !new_edges.iter().any(|edge| tree.crosses_tree(*edge))
+        } else {
+            !tree.tree_edges_cross()
+        };
+        layout.ordered = self.ordered;
+        let path = Edge(old_pos, new_pos);
+
+        let grid = PointGrid::new(&layout.points);
+        let mut inside = vec![false; layout.points.len()];
+        for n in &neighbours {
+            let triangle = [old_pos, new_pos, *n];
+            grid.in_triangle(triangle, |a| inside[a] |= in_polygon(layout.points[a], &triangle));
+        }
+        let (mut min, mut max) = (old_pos, old_pos);
+        for p in &layout.points {
+            (min, max) = (min.min(*p), max.max(*p));
+        }
+        let reach = 2.0 * (max - min).length() + 1.0;
+
+        let mut check = |a: usize, b: usize| {
+            let segment = Edge(layout.points[a], layout.points[b]);
+            let visible = if self.visible[a][b] {
+                !new_edges.iter().any(|edge| segment.crosses(*edge))
+            } else if old_edges.iter().any(|edge| segment.crosses(*edge))
+                && !new_edges.iter().any(|edge| segment.crosses(*edge)) {
+                !tree.crosses_tree(segment)
+            } else {
+                false
+            };
+            self.visible[a][b] = visible;
+            self.visible[b][a] = visible;
+        };
+        for a in (0..layout.points.len()).filter(|a| *a != x) {
+            if inside[a] {
+                (0..layout.points.len()).filter(|b| *b != a && *b != x).for_each(|b| check(a, b));
+                continue;
+            }
+            // Pairs with an end inside a triangle were checked from that end
+            let through_path = |b: usize| {
+                if b > a && b != x && !inside[b] && Edge(layout.points[a], layout.points[b]).intersects(path) {
+                    check(a, b);
+                }
+            };
+            match cone(layout.points[a], path.0.min(path.1), path.0.max(path.1), reach) {
+                Some(triangle) => grid.in_triangle(triangle, through_path),
+                None => (a + 1..layout.points.len()).for_each(through_path),
+            }
+        }
+        self.sweep(&layout, x);
+        for a in 0..self.nodes.len() {
+            self.visible[a][x] = self.visible[x][a];
+        }
+    }
+
+    fn layout(&self, tree: &Tree) -> Layout {
+        let points: Vec<Point2> = self.nodes.iter().map(|index| tree[*index].pos).collect();
+        let mut edges = vec![];
+        for index in &self.nodes {
+            if let TreesEnum::Branch(_) = index.0 {
+                let parent = match tree[*index].parent {
+                    Some(parent) => self.number(TreeIndex(index.0, parent)),
+                    None => 0,
+                };
+                edges.push((self.number(*index), parent));
+            }
+        }
+        let mut incident = vec![vec![]; points.len()];
+        for (i, (a, b)) in edges.iter().enumerate() {
+            incident[*a].push(i);
+            incident[*b].push(i);
+        }
+        Layout { points, edges, incident, ordered: self.ordered }
+    }
+
+    /// Fills in the row of `v` by going around it once.
+    /// Edges whose interior is crossed by the current ray are kept in an active set,
+    /// every node is only checked against the nearest active edge, the edges of the other nodes on the same ray
+    /// and the edges on a line through `v`.
+    fn sweep(&mut self, layout: &Layout, v: usize) {
+        let center = layout.points[v];
+        // Nodes on top of v have no direction, check those against every edge
+        let (coincident, mut others): (Vec<usize>, Vec<usize>) = (0..layout.points.len())
+            .filter(|w| *w != v)
+            .partition(|w| layout.points[*w] == center);
+        for w in coincident {
+            self.set_visible(layout, v, w, 0..layout.edges.len());
+        }
+        sort_by_angle(center, &layout.points, &mut others);
+        let Some(first) = others.first().map(|w| layout.points[*w]) else {
+            return;
+        };
+
+        // Edges on a line through v are never crossed by a ray, but can still contain v
+        let mut collinear = vec![];
+        let mut crossed = vec![];
+        let mut lines = vec![Edge(center, center); layout.edges.len()];
+        for (e, (a, b)) in layout.edges.iter().enumerate() {
+            let (pa, pb) = (layout.points[*a], layout.points[*b]);
+            let orientation = orient2d(center, pa, pb);
+            lines[e] = if orientation > 0.0 { Edge(pa, pb) } else { Edge(pb, pa) };
+            if orientation == 0.0 {
+                if pa != center && pb != center {
+                    collinear.push(e);
+                }
+                continue;
+            }
+            let Edge(cw, ccw) = lines[e];
+            // Crossed by a ray just before the direction of the first node
+            if orient2d(center, cw, first) > 0.0 && orient2d(center, first, ccw) >= 0.0 {
+                crossed.push(e);
+            }
+        }
+        let mut active = ActiveSet::new(layout.ordered, lines);
+        for e in crossed {
+            active.insert(e);
+        }
+
+        let mut joining = vec![];
+        let mut start = 0;
+        while start < others.len() {
+            let mut end = start + 1;
+            while end < others.len()
+                && compare_angle(center, layout.points[others[start]], layout.points[others[end]]) == Ordering::Equal {
+                end += 1;
+            }
+            let group = &others[start..end];
+            for w in group {
+                // The edges of the other nodes on this ray can touch the segment, the edges of w only end on it
+                let on_ray = group.iter().filter(|p| *p != w).flat_map(|p| layout.incident[*p].iter().copied());
+                self.set_visible(layout, v, *w, active.blocking().iter().copied().chain(on_ray).chain(collinear.iter().copied()));
+            }
+            // All edges that end on this ray leave before new ones join, so the active edges are always crossed by one ray
+            joining.clear();
+            for p in group {
+                for e in &layout.incident[*p] {
+                    let (a, b) = layout.edges[*e];
+                    let other = layout.points[if a == *p { b } else { a }];
+                    if a == v || b == v || other == center {
+                        continue;
+                    }
+                    let orientation = orient2d(center, layout.points[*p], other);
+                    if orientation < 0.0 {
+                        active.remove(*e);
+                    } else if orientation > 0.0 {
+                        joining.push(*e);
+                    }
+                }
+            }
+            for e in &joining {
+                active.insert(*e);
+            }
+            start = end;
+        }
+    }
+
+    /// Marks `w` visible from `v` if the segment between them crosses none of the candidate edges.
+    fn set_visible(&mut self, layout: &Layout, v: usize, w: usize, mut candidates: impl Iterator<Item = usize>) {
+        let segment = Edge(layout.points[v], layout.points[w]);
+        self.visible[v][w] = !candidates.any(|e| segment.crosses(layout.edge(e)));
+    }
+}
+
+impl Layout {
+    fn edge(&self, e: usize) -> Edge {
+        Edge(self.points[self.edges[e].0], self.points[self.edges[e].1])
+    }
+}
+
+/// Sorts the nodes like `compare_angle`, first with a cheap pseudo angle and then exactly.
+/// The pseudo angle only puts nodes in nearly the same direction in the wrong order,
+/// so the insertion sort that fixes those only has to move a few of them.
+fn sort_by_angle(center: Point2, points: &[Point2], nodes: &mut [usize]) {
+    let pseudo_angle = |p: Point2| {
+        let (dx, dy) = ((p.x - center.x) as f64, (p.y - center.y) as f64);
+        let t = dy.abs() / (dx.abs() + dy.abs());
+        match (dx >= 0.0, dy >= 0.0) {
+            (true, true) => t,
+            (false, true) => 2.0 - t,
+            (false, false) => 2.0 + t,
+            (true, false) => 4.0 - t,
+        }
+    };
+    let mut keys: Vec<(f64, usize)> = nodes.iter().map(|w| (pseudo_angle(points[*w]), *w)).collect();
+    keys.sort_unstable_by(|a, b| a.0.total_cmp(&b.0));
+    for (node, (_, w)) in nodes.iter_mut().zip(keys) {
+        *node = w;
+    }
+    for i in 1..nodes.len() {
+        let mut j = i;
+        while j > 0 && compare_angle(center, points[nodes[j - 1]], points[nodes[j]]) == Ordering::Greater {
+            nodes.swap(j - 1, j);
+            j -= 1;
+        }
+    }
+}
+
+/// Orders points around `center` counterclockwise starting at the positive x axis,
+/// points in exactly the same direction are equal.
+fn compare_angle(center: Point2, a: Point2, b: Point2) -> Ordering {
+    let half = |p: Point2| {
+        let (dx, dy) = (p.x - center.x, p.y - center.y);
+        if dy > 0.0 || (dy == 0.0 && dx > 0.0) { 0 } else { 1 }
+    };
+    half(a).cmp(&half(b)).then_with(|| {
+        let orientation = orient2d(center, a, b);
+        if orientation > 0.0 {
+            Ordering::Less
+        } else if orientation < 0.0 {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        }
+    })
+}
+
+/// The triangle from `apex` that holds every point whose segment from `apex` meets the box from `low` to `high`,
+/// as far as `reach`. `None` if `apex` is too close to the box for a narrow triangle.
+fn cone(apex: Point2, low: Point2, high: Point2, reach: f32) -> Option<[Point2; 3]> {
+    // Grow the box so rounding can't move a segment out of the triangle
+    let size = (high - low).max_element() * 1e-3 + apex.abs().max(low.abs()).max(high.abs()).max_element() * 1e-4;
+    let margin = Point2::splat(size + 1e-2);
+    let (low, high) = (low - margin, high + margin);
+    if apex.cmpge(low).all() && apex.cmple(high).all() {
+        return None;
+    }
+    let corners = [low, Point2::new(high.x, low.y), high, Point2::new(low.x, high.y)].map(|corner| corner - apex);
+    let cross = |a: Point2, b: Point2| a.x * b.y - a.y * b.x;
+    let right = corners.into_iter().find(|a| corners.iter().all(|b| cross(*a, *b) >= 0.0))?;
+    let left = corners.into_iter().find(|a| corners.iter().all(|b| cross(*b, *a) >= 0.0))?;
+    // Wider than a right angle the far side of the triangle would come too close to the apex
+    if right.dot(left) < 0.0 {
+        return None;
+    }
+    Some([apex, apex + right.normalize() * reach, apex + left.normalize() * reach])
+}
+
+/// The edges crossed by the current ray.
+/// While no two edges cross they keep their order along the ray, then the members are a binary heap
+/// with the nearest edge first and only that one can hide a node. Otherwise all of them have to be checked.
+struct ActiveSet {
+    members: Vec<usize>,
+    position: Vec<Option<usize>>,
+    order: Option<DistanceOrder>,
+}
+
+/// Compares edges by their distance from the center of a sweep.
+struct DistanceOrder {
+    /// Every edge, pointing so that the center is on its left
+    lines: Vec<Edge>,
+}
+
+impl ActiveSet {
+    fn new(ordered: bool, lines: Vec<Edge>) -> Self {
+        Self {
+            members: vec![],
+            position: vec![None; lines.len()],
+            order: ordered.then_some(DistanceOrder { lines }),
+        }
+    }
+
+    fn insert(&mut self, e: usize) {
+        if self.position[e].is_none() {
+            self.position[e] = Some(self.members.len());
+            self.members.push(e);
+            self.sift_up(self.members.len() - 1);
+        }
+    }
+
+    fn remove(&mut self, e: usize) {
+        if let Some(i) = self.position[e].take() {
+            self.members.swap_remove(i);
+            if let Some(moved) = self.members.get(i) {
+                self.position[*moved] = Some(i);
+                self.sift_up(i);
+                self.sift_down(i);
+            }
+        }
+    }
+
+    /// The active edges that can hide a node on the current ray.
+    fn blocking(&self) -> &[usize] {
+        match self.order {
+            Some(_) => &self.members[..self.members.len().min(1)],
+            None => &self.members,
+        }
+    }
+
+    fn closer(&self, i: usize, j: usize) -> bool {
+        self.order.as_ref().is_some_and(|order| order.compare(self.members[i], self.members[j]) == Ordering::Less)
+    }
+
+    fn swap(&mut self, i: usize, j: usize) {
+        self.members.swap(i, j);
+        self.position[self.members[i]] = Some(i);
+        self.position[self.members[j]] = Some(j);
+    }
+
+    fn sift_up(&mut self, mut i: usize) {
+        while i > 0 && self.closer(i, (i - 1) / 2) {
+            self.swap(i, (i - 1) / 2);
+            i = (i - 1) / 2;
+        }
+    }
+
+    fn sift_down(&mut self, mut i: usize) {
+        loop {
+            let mut closest = i;
+            for child in [2 * i + 1, 2 * i + 2] {
+                if child < self.members.len() && self.closer(child, closest) {
+                    closest = child;
+                }
+            }
+            if closest == i {
+                return;
+            }
+            self.swap(i, closest);
+            i = closest;
+        }
+    }
+}
+
+impl DistanceOrder {
+    /// Orders two edges that are crossed by the same ray and don't cross each other, the one closer to the center first.
+    /// Ties are broken by the edge ids, so an edge is only equal to itself.
+    fn compare(&self, e: usize, f: usize) -> Ordering {
+        if e == f {
+            return Ordering::Equal;
+        }
+        let (e_line, f_line) = (self.lines[e], self.lines[f]);
+        let order = match (side(f_line, e_line.0), side(f_line, e_line.1)) {
+            (0, 0) => Ordering::Equal,
+            (a, b) if a >= 0 && b >= 0 => Ordering::Less,
+            (a, b) if a <= 0 && b <= 0 => Ordering::Greater,
+            // `e` reaches over the line through `f`, so `f` lies on one side of `e`
+            _ => if side(e_line, f_line.0) >= 0 && side(e_line, f_line.1) >= 0 { Ordering::Greater } else { Ordering::Less },
+        };
+        order.then(e.cmp(&f))
+    }
+}
+
+/// 1 if `p` is left of `line`, on the same side as the center, -1 if it's right of it and 0 on the line.
+fn side(line: Edge, p: Point2) -> i32 {
+    sign(orient2d(line.0, line.1, p))
+}
+
+fn sign(x: f64) -> i32 {
+    if x > 0.0 {
+        1
+    } else if x < 0.0 {
+        -1
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use nannou::geom::Point2;
+    use proptest::prelude::*;
+    use crate::random::random_trees;
+    use crate::tree::Tree;
+    use crate::tree_index::TreeIndex;
+
+    /// Random trees with the nodes rounded to a coarse grid, so there are lots of collinear nodes.
+    fn grid_tree() -> impl Strategy<Value = Tree> {
+        random_trees(1..5, 3..6).prop_map(|mut tree| {
+            let nodes: Vec<TreeIndex> = tree.iter().collect();
+            for index in nodes {
+                tree[index].pos = (tree[index].pos / 20.0).round() * 20.0;
+            }
+            tree
+        })
+    }
+
+    /// Trees straight from the generator, whose edges never cross, and rounded ones that often have crossings.
+    fn any_tree() -> impl Strategy<Value = Tree> {
+        prop_oneof![random_trees(1..5, 3..6), grid_tree()]
+    }
+
+    fn brute_force(tree: &Tree, index: TreeIndex) -> Vec<TreeIndex> {
+        tree.check_node_vis_from_edge(index, tree.get_all_edges())
+    }
+
+    proptest! {
+        #[test]
+        fn sweep_matches_brute_force(tree in any_tree()) {
+            for index in tree.iter() {
+                prop_assert_eq!(tree.check_node_vis(index), brute_force(&tree, index));
+            }
+        }
+
+        #[test]
+        fn moving_a_node_matches_a_rebuild(tree in any_tree(), node in any::<prop::sample::Index>(), x in -10..10i32, y in -10..10i32) {
+            let mut tree = tree;
+            let nodes: Vec<TreeIndex> = tree.iter().collect();
+            let moved = nodes[node.index(nodes.len())];
+            tree.visibility();
+            tree.move_node(moved, tree[moved].pos + Point2::new(x as f32, y as f32) * 20.0);
+            for index in tree.iter() {
+                prop_assert_eq!(tree.check_node_vis(index), brute_force(&tree, index));
+            }
+        }
+
+        #[test]
+        fn several_moves_match_a_rebuild(tree in any_tree(), moves in prop::collection::vec((any::<prop::sample::Index>(), -5..5i32, -5..5i32), 1..5)) {
+            let mut tree = tree;
+            let nodes: Vec<TreeIndex> = tree.iter().collect();
+            tree.visibility();
+            for (node, x, y) in moves {
+                let moved = nodes[node.index(nodes.len())];
+                tree.move_node(moved, tree[moved].pos + Point2::new(x as f32, y as f32) * 20.0);
+            }
+            for index in tree.iter() {
+                prop_assert_eq!(tree.check_node_vis(index), brute_force(&tree, index));
+            }
+        }
+    }
+}
+